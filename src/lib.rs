@@ -1,22 +1,38 @@
 extern crate flate2;
 extern crate rust_htslib;
+extern crate zstd;
+extern crate bzip2;
+extern crate gzp;
+extern crate libc;
 
 use std::io::Error;
+use std::io::ErrorKind;
+use std::fmt;
+use std::str::FromStr;
 
-use flate2::read::GzDecoder;
+use flate2::read::MultiGzDecoder;
 use flate2::write::GzEncoder;
+use flate2::write::DeflateEncoder;
+use flate2::read::DeflateDecoder;
+use bzip2::read::MultiBzDecoder;
+use bzip2::write::BzEncoder;
 
 use std::io::BufReader;
 use std::io::BufWriter;
 use std::io::BufRead;
 use std::io::Write;
+use std::io::Read as StdRead; // plain Read is htslib's, from the prelude
+use std::io::Seek;
 use std::fs::File;
+use std::collections::VecDeque;
+use std::collections::HashMap;
+use std::collections::HashSet;
 
-use rust_htslib::sam;
 use rust_htslib::bam;
+use rust_htslib::htslib;
 use rust_htslib::prelude::*;
 
-#[derive(Debug)]
+#[derive(Debug,PartialEq,Clone)]
 pub enum DnaFormat {
     Fastq,
     Fasta,
@@ -24,20 +40,395 @@ pub enum DnaFormat {
     Sam,
     Cram, 
     TwoBit,
+    Raw, // one bare sequence per line, no names
+    Tsv, // name<TAB>seq[<TAB>qual] per line, for sort/join
 }
 use DnaFormat::*;
 
+// lowercase names, what FromStr takes back. eg for a --format argument
+impl fmt::Display for DnaFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Fastq => "fastq",
+            Fasta => "fasta",
+            Bam => "bam",
+            Sam => "sam",
+            Cram => "cram",
+            TwoBit => "2bit",
+            Raw => "raw",
+            Tsv => "tsv",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for DnaFormat {
+    type Err = DnaError;
+    fn from_str(name: &str) -> Result<Self, DnaError> {
+        match name.to_ascii_lowercase().as_str() {
+            "fastq" | "fq" => Ok(Fastq),
+            "fasta" | "fa" => Ok(Fasta),
+            "bam" => Ok(Bam),
+            "sam" => Ok(Sam),
+            "cram" => Ok(Cram),
+            "2bit" => Ok(TwoBit),
+            "raw" | "txt" | "seq" => Ok(Raw),
+            "tsv" => Ok(Tsv),
+            _ => Err(DnaError::Parse(format!("unknown format {}", name))),
+        }
+    }
+}
+
 #[derive(Debug,PartialEq,Clone)]
 pub enum Compression {
     Gzipped,
+    Bgzf,
+    Zstd,
+    Bzip2,
     Uncompressed,
 }
 use Compression::*;
 
+#[derive(Debug)]
+pub enum DnaError {
+    UnknownExtension(String),
+    Io(Error),
+    Unsupported(String),
+    Parse(String),
+}
+
+impl fmt::Display for DnaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DnaError::UnknownExtension(ref filename) => write!(f, "format of file {} not supported or file has no extension", filename),
+            DnaError::Io(ref err) => write!(f, "io error: {}", err),
+            DnaError::Unsupported(ref msg) => write!(f, "unsupported: {}", msg),
+            DnaError::Parse(ref msg) => write!(f, "parse error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for DnaError {}
+
+impl From<Error> for DnaError {
+    fn from(err: Error) -> Self { DnaError::Io(err) }
+}
+
+#[derive(Default,Clone,PartialEq,Eq,Debug)]
 pub struct DnaRecord {
     pub seq: String,
     pub qual: Option<String>,
     pub name: String,
+    pub description: Option<String>, // whatever follows the id on a fasta header line
+    // alignment info, only filled in by the bam/cram readers. pos is 0-based like htslib
+    pub flags: Option<u16>,
+    pub tid: Option<i32>,
+    pub pos: Option<i64>,
+    pub mapq: Option<u8>,
+    pub tags: Option<HashMap<String, String>>, // bam aux tags asked for with BamReader::with_tags, eg CB and UB
+}
+
+impl DnaRecord {
+    pub fn reverse_complement(&self) -> DnaRecord {
+        self.with_seq_qual(
+            self.seq.chars().rev().map(complement).collect(),
+            self.qual.as_ref().map(|q| q.chars().rev().collect()),
+        )
+    }
+    // the same read with a new seq/qual, everything else carried over
+    fn with_seq_qual(&self, seq: String, qual: Option<String>) -> DnaRecord {
+        DnaRecord{
+            name: self.name.clone(),
+            description: self.description.clone(),
+            seq,
+            qual,
+            flags: self.flags,
+            tid: self.tid,
+            pos: self.pos,
+            mapq: self.mapq,
+            tags: self.tags.clone(),
+        }
+    }
+    // qual as plain phred scores, offset is 33 for sanger/illumina 1.8+ and 64 for old illumina
+    pub fn phred_scores(&self, offset: u8) -> Option<Vec<u8>> {
+        self.qual.as_ref().map(|q| q.bytes().map(|x| x.saturating_sub(offset)).collect())
+    }
+    // chance each base call is wrong, 10^(-Q/10)
+    pub fn error_probabilities(&self, offset: u8) -> Option<Vec<f64>> {
+        self.phred_scores(offset).map(|scores| scores.iter().map(|&q| 10f64.powf(-(q as f64) / 10.0)).collect())
+    }
+    // plain mean of the phred scores, None with no qual or no bases
+    pub fn average_quality(&self, offset: u8) -> Option<f64> {
+        let scores = self.phred_scores(offset)?;
+        if scores.is_empty() { return None; }
+        Some(scores.iter().map(|&q| q as f64).sum::<f64>() / scores.len() as f64)
+    }
+    pub fn rescale_quality(&self, from: u8, to: u8) -> DnaRecord {
+        self.with_seq_qual(
+            self.seq.clone(),
+            self.phred_scores(from).map(|scores| scores.iter().map(|x| x.saturating_add(to) as char).collect()),
+        )
+    }
+    // bwa style 3' trim, cut where the running sum of threshold - qual from the end peaks
+    pub fn trim_quality(&self, offset: u8, threshold: u8) -> DnaRecord {
+        let scores = match self.phred_scores(offset) {
+            Some(scores) => scores,
+            None => return self.with_seq_qual(self.seq.clone(), None),
+        };
+        let mut sum: i32 = 0;
+        let mut best: i32 = 0;
+        let mut cut = scores.len();
+        for i in (0..scores.len()).rev() {
+            sum += threshold as i32 - scores[i] as i32;
+            if sum < 0 { break; }
+            if sum > best {
+                best = sum;
+                cut = i;
+            }
+        }
+        self.slice(0, cut)
+    }
+    // solid colorspace, a primer base then colors 0-3 for each change between two bases, eg T0123 is TGAT.
+    // the primer is dropped and so is its qual when there is one. a . color makes that base and everything after it N.
+    // None when seq isnt a base followed by colors
+    pub fn decode_colorspace(&self) -> Option<DnaRecord> {
+        let code = |base: u8| match base.to_ascii_uppercase() { b'A' => Some(0u8), b'C' => Some(1), b'G' => Some(2), b'T' => Some(3), _ => None };
+        let bytes = self.seq.as_bytes();
+        if bytes.len() < 2 { return None; }
+        let mut prev = Some(code(bytes[0])?);
+        if !bytes[1..].iter().all(|&color| matches!(color, b'0'..=b'3' | b'.')) { return None; }
+        let seq: String = bytes[1..].iter().map(|&color| {
+            prev = match (prev, color) {
+                (Some(base), b'0'..=b'3') => Some(base ^ (color - b'0')),
+                _ => None,
+            };
+            match prev { Some(base) => b"ACGT"[base as usize] as char, None => 'N' }
+        }).collect();
+        let qual = self.qual.as_ref().map(|qual| if qual.len() == bytes.len() { qual[1..].to_string() } else { qual.clone() });
+        Some(self.with_seq_qual(seq, qual))
+    }
+    // cut at the start of the first window whose mean quality is under min_avg, like trimmomatic SLIDINGWINDOW.
+    // reads shorter than the window are one window
+    pub fn sliding_window_trim(&self, offset: u8, window: usize, min_avg: u8) -> DnaRecord {
+        let scores = match self.phred_scores(offset) {
+            Some(scores) => scores,
+            None => return self.with_seq_qual(self.seq.clone(), None),
+        };
+        let window = std::cmp::min(window, scores.len());
+        if window == 0 { return self.slice(0, scores.len()); }
+        let min_sum = min_avg as u32 * window as u32;
+        let mut sum: u32 = scores[..window].iter().map(|&q| q as u32).sum();
+        for start in 0..scores.len() - window + 1 {
+            if start > 0 { sum = sum + scores[start + window - 1] as u32 - scores[start - 1] as u32; }
+            if sum < min_sum { return self.slice(0, start); }
+        }
+        self.slice(0, scores.len())
+    }
+    // exactly len bases, long reads are cut and short ones padded on the end with pad_base. pad_qual is the
+    // ascii qual character, eg b'N' and b'!', and is only used when there is a qual
+    pub fn fit_to_length(&self, len: usize, pad_base: u8, pad_qual: u8) -> DnaRecord {
+        if self.seq.len() >= len { return self.slice(0, len); }
+        let extra = len - self.seq.len();
+        let mut seq = self.seq.clone();
+        seq.extend(std::iter::repeat_n(pad_base as char, extra));
+        let qual = self.qual.as_ref().map(|qual| {
+            let mut qual = qual.clone();
+            qual.extend(std::iter::repeat_n(pad_qual as char, extra));
+            qual
+        });
+        self.with_seq_qual(seq, qual)
+    }
+    // N runs off both ends, the Ns in the middle stay. an all N read comes back empty
+    pub fn trim_ns(&self) -> DnaRecord {
+        let is_n = |base: &u8| *base == b'N' || *base == b'n';
+        let bytes = self.seq.as_bytes();
+        let start = bytes.iter().position(|base| !is_n(base)).unwrap_or(bytes.len());
+        let end = bytes.iter().rposition(|base| !is_n(base)).map_or(start, |last| last + 1);
+        self.slice(start, end)
+    }
+    // seq and qual cut down to [start, end), both are ascii so byte offsets are fine. each is clamped to its
+    // own length so a qual shorter (or longer) than seq, eg from a truncated fastq, doesnt panic
+    fn slice(&self, start: usize, end: usize) -> DnaRecord {
+        let cut = |s: &str| { let end = std::cmp::min(end, s.len()); s[std::cmp::min(start, end)..end].to_string() };
+        self.with_seq_qual(cut(&self.seq), self.qual.as_ref().map(|q| cut(q)))
+    }
+    // soft masked (lowercase) bases become N
+    pub fn hard_mask(&self) -> DnaRecord {
+        let seq = self.seq.chars().map(|base| if base.is_ascii_lowercase() { 'N' } else { base }).collect();
+        self.with_seq_qual(seq, self.qual.clone())
+    }
+    pub fn uppercase(&self) -> DnaRecord {
+        self.with_seq_qual(self.seq.to_ascii_uppercase(), self.qual.clone())
+    }
+    // for matching names across files, eg a bam qname against the fastq it came from
+    pub fn name_stem(&self) -> &str { pair_stem(&self.name) }
+    pub fn len(&self) -> usize { self.seq.len() }
+    pub fn is_empty(&self) -> bool { self.seq.is_empty() }
+    // fraction of called bases that are G or C, Ns dont count either way
+    pub fn gc_content(&self) -> f64 {
+        let mut gc = 0;
+        let mut called = 0;
+        for base in self.seq.bytes() {
+            match base {
+                b'G' | b'C' | b'g' | b'c' => { gc += 1; called += 1; },
+                b'N' | b'n' => (),
+                _ => called += 1,
+            }
+        }
+        if called == 0 { return 0.0; }
+        gc as f64 / called as f64
+    }
+    // the same text FastaWriter writes with its default 60 column lines
+    pub fn to_fasta_string(&self) -> String { fasta_string(self, Some(FASTA_LINE_WIDTH)) }
+    pub fn to_fastq_string(&self) -> Result<String, DnaError> {
+        match self.qual {
+            Some(ref qual) => Ok(format!("{}\n{}\n+\n{}\n", fastq_header(self), self.seq, qual)),
+            None => Err(DnaError::Unsupported(format!("record {} has no quality, cant make fastq out of it", self.name))),
+        }
+    }
+    // None for anything that isnt casava 1.8+ style, a /1 or /2 on the end is fine
+    pub fn illumina_header(&self) -> Option<IlluminaHeader> {
+        let fields: Vec<&str> = pair_stem(&self.name).split(':').collect();
+        if fields.len() != 7 || fields[0].is_empty() { return None; }
+        // the comment is still on the name for fastq, fasta readers split it off into the description
+        let comment = match self.name.find(char::is_whitespace) {
+            Some(x) => Some(self.name[x..].trim()),
+            None => self.description.as_ref().map(|x| x.trim()),
+        };
+        let parsed = comment.and_then(|x| x.split_whitespace().next()).and_then(parse_illumina_comment);
+        Some(IlluminaHeader{
+            instrument: fields[0].to_string(),
+            run: fields[1].parse().ok()?,
+            flowcell: fields[2].to_string(),
+            lane: fields[3].parse().ok()?,
+            tile: fields[4].parse().ok()?,
+            x: fields[5].parse().ok()?,
+            y: fields[6].parse().ok()?,
+            read: parsed.as_ref().map(|x| x.0),
+            filtered: parsed.as_ref().map(|x| x.1),
+            control: parsed.as_ref().map(|x| x.2),
+            index: parsed.map(|x| x.3),
+        })
+    }
+    // bits per base over how often each base shows up, 0 for a homopolymer up to 2 for an even ACGT mix.
+    // case is ignored
+    pub fn shannon_entropy(&self) -> f64 {
+        if self.seq.is_empty() { return 0.0; }
+        let mut counts = [0usize; 256];
+        for base in self.seq.bytes() { counts[base.to_ascii_uppercase() as usize] += 1; }
+        let len = self.seq.len() as f64;
+        counts.iter().filter(|&&count| count > 0).map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        }).sum()
+    }
+    // one pass qc counts, case doesnt matter for the homopolymer and Ns dont count as one
+    pub fn base_stats(&self) -> BaseStats {
+        let mut stats = BaseStats{ length: self.seq.len(), ..Default::default() };
+        let mut run = 0;
+        let mut last = 0u8;
+        for base in self.seq.bytes() {
+            if base.is_ascii_lowercase() { stats.lowercase_count += 1; }
+            let upper = base.to_ascii_uppercase();
+            match upper {
+                b'N' => stats.n_count += 1,
+                b'G' | b'C' => stats.gc_count += 1,
+                _ => (),
+            }
+            if upper == b'N' { run = 0; }
+            else if upper == last { run += 1; }
+            else { run = 1; }
+            last = upper;
+            if run > stats.max_homopolymer { stats.max_homopolymer = run; }
+        }
+        stats
+    }
+}
+
+// @INSTRUMENT:RUN:FLOWCELL:LANE:TILE:X:Y READ:FILTER:CONTROL:INDEX, the part after the space is
+// None when its missing or isnt in this format
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub struct IlluminaHeader {
+    pub instrument: String,
+    pub run: u32,
+    pub flowcell: String,
+    pub lane: u32,
+    pub tile: u32,
+    pub x: u32,
+    pub y: u32,
+    pub read: Option<u8>, // 1 or 2 for the mates
+    pub filtered: Option<bool>, // Y in the file, failed the chastity filter
+    pub control: Option<u32>,
+    pub index: Option<String>, // sample barcode, eg ACGT or ACGT+TTGA for dual
+}
+
+fn parse_illumina_comment(comment: &str) -> Option<(u8, bool, u32, String)> {
+    let fields: Vec<&str> = comment.split(':').collect();
+    if fields.len() != 4 { return None; }
+    let filtered = match fields[1] {
+        "Y" => true,
+        "N" => false,
+        _ => return None,
+    };
+    Some((fields[0].parse().ok()?, filtered, fields[2].parse().ok()?, fields[3].to_string()))
+}
+
+#[derive(Default,Clone,PartialEq,Eq,Debug)]
+pub struct BaseStats {
+    pub length: usize,
+    pub n_count: usize,
+    pub lowercase_count: usize, // soft masked bases
+    pub gc_count: usize,
+    pub max_homopolymer: usize,
+}
+
+impl BaseStats {
+    // for whole file totals, eg reader.map(|rec| rec.base_stats()).fold(BaseStats::default(), |a, b| a.merge(&b))
+    pub fn merge(mut self, other: &BaseStats) -> BaseStats {
+        self.length += other.length;
+        self.n_count += other.n_count;
+        self.lowercase_count += other.lowercase_count;
+        self.gc_count += other.gc_count;
+        self.max_homopolymer = std::cmp::max(self.max_homopolymer, other.max_homopolymer);
+        self
+    }
+}
+
+// whole file summary from DnaReader::stats, like seqkit stats. gc_percent leaves Ns out same as gc_content
+#[derive(Default,Clone,PartialEq,Debug)]
+pub struct SeqStats {
+    pub num_records: u64,
+    pub total_length: u64,
+    pub min_length: usize,
+    pub max_length: usize,
+    pub mean_length: f64,
+    pub n50: usize,
+    pub gc_percent: f64,
+}
+
+impl fmt::Display for SeqStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "num_records   {}", self.num_records)?;
+        writeln!(f, "total_length  {}", self.total_length)?;
+        writeln!(f, "min_length    {}", self.min_length)?;
+        writeln!(f, "max_length    {}", self.max_length)?;
+        writeln!(f, "mean_length   {:.1}", self.mean_length)?;
+        writeln!(f, "n50           {}", self.n50)?;
+        write!(f, "gc_percent    {:.2}", self.gc_percent)
+    }
+}
+
+// iupac complements, keeps the case so soft masking survives
+fn complement(base: char) -> char {
+    match base {
+        'A' => 'T', 'T' => 'A', 'C' => 'G', 'G' => 'C',
+        'a' => 't', 't' => 'a', 'c' => 'g', 'g' => 'c',
+        'R' => 'Y', 'Y' => 'R', 'K' => 'M', 'M' => 'K',
+        'r' => 'y', 'y' => 'r', 'k' => 'm', 'm' => 'k',
+        'B' => 'V', 'V' => 'B', 'D' => 'H', 'H' => 'D',
+        'b' => 'v', 'v' => 'b', 'd' => 'h', 'h' => 'd',
+        x => x, // N, S, W are their own complement
+    }
 }
 
 pub trait DnaRead {
@@ -45,14 +436,35 @@ pub trait DnaRead {
     fn my_type(&self) -> DnaFormat;
     fn header(&self) -> Option<bam::Header>;
     fn extension(&self) -> String;
+    fn fetch(&mut self, _region: &str) -> Result<(), DnaError> {
+        Err(DnaError::Unsupported(format!("fetch needs an indexed bam, not {:?}", self.my_type())))
+    }
+    // next but handing back bad records as errors, only the fastq and fasta readers tell them apart so far
+    fn try_next(&mut self) -> Result<Option<DnaRecord>, DnaError> { Ok(self.next()) }
+    // uses up the rest of the reader, formats that can count without building records override this
+    fn count_records(&mut self) -> u64 {
+        let mut count = 0;
+        while self.next().is_some() { count += 1; }
+        count
+    }
+    // back to the first record, only works for readers that know the path they were opened from
+    fn reset(&mut self) -> Result<(), DnaError> {
+        Err(DnaError::Unsupported(format!("this {:?} reader cant go back to the start", self.my_type())))
+    }
 }
 
 pub trait DnaWrite {
     fn write(&mut self, rec: &DnaRecord) -> Result<(), Error>;
+    fn flush(&mut self) -> Result<(), Error>;
+    // for progress bars. bytes are before any compression, writers that dont keep count say 0
+    fn records_written(&self) -> u64 { 0 }
+    fn bytes_written(&self) -> u64 { 0 }
 }
 
 pub struct DnaReader {
     pub reader: Box<DnaRead>,
+    peeked: VecDeque<DnaRecord>,
+    validate_alphabet: bool,
 }
 
 pub struct DnaWriter {
@@ -60,130 +472,1639 @@ pub struct DnaWriter {
 }
 
 fn check_extension(filename: &str) -> (DnaFormat, Compression) {
+    match extension_format(filename) {
+        Some(x) => x,
+        None => panic!("format of file {} not supported or file has no extension",filename),
+    }
+}
+
+fn extension_format(filename: &str) -> Option<(DnaFormat, Compression)> {
     let filetype = filename.split(".").collect::<Vec<&str>>();
-    assert!(filetype.len() >= 2, "file {} has no extension", filename);
-    match filetype[filetype.len()-1] {
-        "gz" => {
-            assert!(filetype.len() >= 3, "gz file {} has no format extension", filename);
+    if filetype.len() < 2 { return None; }
+    let fmt = match filetype[filetype.len()-1] {
+        "gz" | "bgz" | "zst" | "zstd" | "bz2" => {
+            if filetype.len() < 3 { return None; }
+            let compression = match filetype[filetype.len()-1] {
+                "gz" => Gzipped,
+                "bgz" => Bgzf, // what bgzip calls them sometimes
+                "bz2" => Bzip2,
+                _ => Zstd,
+            };
             match filetype[filetype.len()-2] {
-                "fa" | "fasta" => (Fasta, Gzipped),
-                "fq" | "fastq" => (Fastq, Gzipped),
-                _ => panic!("format of file {} not supported",filename),
+                "fa" | "fasta" | "fna" | "ffn" | "frn" => (Fasta, compression),
+                "fq" | "fastq" => (Fastq, compression),
+                "txt" | "seq" => (Raw, compression),
+                "tsv" => (Tsv, compression),
+                "sam" => (Sam, compression),
+                _ => return None,
             }
         },
         "fastq" | "fq" => (Fastq, Uncompressed),
-        "fasta" | "fa" => (Fasta, Uncompressed),
+        "fasta" | "fa" | "fna" | "ffn" | "frn" => (Fasta, Uncompressed), // fna/ffn/frn are ncbi's names
         "sam" => (Sam, Uncompressed),
         "bam" => (Bam, Gzipped), // this isnt strictly true, can have uncompressed bam, but bam library will deal with this
         "cram" => (Cram, Gzipped), // same, also unimplemented
-        "2Bit" => (TwoBit, Uncompressed), //unimplemented
-        _ => panic!("format of file {} not supported ",filename),
+        "2Bit" | "2bit" => (TwoBit, Uncompressed),
+        "txt" | "seq" => (Raw, Uncompressed),
+        "tsv" => (Tsv, Uncompressed),
+        _ => return None,
+    };
+    Some(fmt)
+}
+
+// guess from the first few bytes of the file, None for whatever we cant tell from content alone
+fn sniff_format(bytes: &[u8]) -> (Option<DnaFormat>, Option<Compression>) {
+    if bytes.starts_with(&[0x1f, 0x8b]) { return (None, Some(Gzipped)); } // also bgzf, so could be bam
+    if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) { return (None, Some(Zstd)); }
+    if bytes.starts_with(b"BZh") { return (None, Some(Bzip2)); }
+    if bytes.starts_with(b"BAM\x01") { return (Some(Bam), Some(Uncompressed)); }
+    for tag in ["@HD\t", "@SQ\t", "@RG\t", "@PG\t", "@CO\t"].iter() {
+        if bytes.starts_with(tag.as_bytes()) { return (Some(Sam), Some(Uncompressed)); }
+    }
+    match bytes.first() {
+        Some(&b'@') => (Some(Fastq), Some(Uncompressed)),
+        Some(&b'>') => (Some(Fasta), Some(Uncompressed)),
+        _ => (None, None),
+    }
+}
+
+// the first bytes after decompressing, eg @ inside a gzip. goes through its own handle on the file so
+// the stream that actually gets read still starts at the beginning
+fn sniff_compressed(filename: &str, compression: &Compression) -> Option<DnaFormat> {
+    let file = open_file(filename).ok()?;
+    let mut decompressed = get_reader(file, compression.clone(), 64 * 1024);
+    let bytes = decompressed.fill_buf().ok()?;
+    match sniff_format(bytes) {
+        (Some(file_fmt), _) => Some(file_fmt),
+        _ => None,
+    }
+}
+
+fn compression_extension(compression: &Compression) -> &'static str {
+    match *compression {
+        Gzipped | Bgzf => ".gz",
+        Zstd => ".zst",
+        Bzip2 => ".bz2",
+        Uncompressed => "",
     }
 }
 
 impl DnaReader {
+    // wrap any reader, eg a BamReader with its filters set
+    pub fn new(reader: Box<DnaRead>) -> Self {
+        DnaReader{ reader, peeked: VecDeque::new(), validate_alphabet: false }
+    }
     pub fn from_path(filename: &str) -> Self {
-        let (file_fmt, compression) = check_extension(filename);
+        match DnaReader::try_from_path(filename) {
+            Ok(reader) => reader,
+            Err(err) => panic!("could not open {}: {}",filename,err),
+        }
+    }
+    pub fn try_from_path(filename: &str) -> Result<Self, DnaError> {
+        DnaReaderBuilder::new().build(filename)
+    }
+    // one file after the other as a single stream, eg fastqs split by lane. they all have to be the same format and compression
+    pub fn from_paths(filenames: &[&str]) -> Result<Self, DnaError> {
+        if filenames.is_empty() { return Err(DnaError::Unsupported("from_paths needs at least one file".to_string())); }
+        let kind = match extension_format(filenames[0]) {
+            Some(x) => x,
+            None => return Err(DnaError::UnknownExtension(filenames[0].to_string())),
+        };
+        let mut readers = Vec::new();
+        for filename in filenames {
+            if extension_format(filename).as_ref() != Some(&kind) {
+                return Err(DnaError::Unsupported(format!("{} is not {:?} {:?} like {}", filename, kind.0, kind.1, filenames[0])));
+            }
+            readers.push(DnaReader::try_from_path_with_format(filename, kind.0.clone(), kind.1.clone())?.reader);
+        }
+        Ok(DnaReader::new(Box::new(ChainedReader{ readers, current: 0 })))
+    }
+    // eg DnaReader::builder().buffer_size(1 << 20).validate(true).build(path)
+    pub fn builder() -> DnaReaderBuilder { DnaReaderBuilder::new() }
+    // for files whose name says nothing useful, eg a fastq called input.dat
+    pub fn from_path_with_format(filename: &str, format: DnaFormat, compression: Compression) -> Self {
+        match DnaReader::try_from_path_with_format(filename, format, compression) {
+            Ok(reader) => reader,
+            Err(err) => panic!("could not open {}: {}",filename,err),
+        }
+    }
+    pub fn try_from_path_with_format(filename: &str, file_fmt: DnaFormat, compression: Compression) -> Result<Self, DnaError> {
         let reader: Box<DnaRead> = match file_fmt {
-            Fasta => Box::new(FastaReader::new(filename, compression)),
-            Fastq => Box::new(FastqReader::new(filename, compression)),
-            Bam => Box::new(BamReader::new(filename)),
-            Sam => Box::new(SamReader::new(filename)),
-            _ => panic!("file extension type {:?} not accepted.",file_fmt),
+            Fasta => Box::new(FastaReader::new(filename, compression)?),
+            Fastq => Box::new(FastqReader::new(filename, compression)?),
+            Bam => Box::new(BamReader::new(filename)?),
+            Sam => Box::new(SamReader::new(filename, compression)?),
+            Cram => Box::new(CramReader::new(filename)?),
+            TwoBit => Box::new(TwoBitReader::new(filename)?),
+            Raw => Box::new(RawReader::new(filename, compression)?),
+            Tsv => Box::new(TsvReader::new(filename, compression)?),
+        };
+        Ok(DnaReader::new(reader))
+    }
+    // bigger buffers for fasta/fastq, htslib does its own buffering for the rest
+    pub fn from_path_with_buffer_size(filename: &str, buffer_size: usize) -> Self {
+        match DnaReaderBuilder::new().buffer_size(buffer_size).build(filename) {
+            Ok(reader) => reader,
+            Err(err) => panic!("could not open {}: {}",filename,err),
+        }
+    }
+    // bgzipped fasta/fastq get decompressed over that many threads. plain gzip cant be split, so it and
+    // everything else only take 1. asking for more threads than the file can use, or 0, is Unsupported rather
+    // than quietly ignored, with 1 the file is read the same as from_path
+    pub fn from_path_with_threads(filename: &str, threads: usize) -> Result<Self, DnaError> {
+        if threads == 0 { return Err(DnaError::Unsupported(format!("{} cant be read on 0 threads", filename))); }
+        let (file_fmt, compression) = match extension_format(filename) {
+            Some(x) => x,
+            None => return Err(DnaError::UnknownExtension(filename.to_string())),
+        };
+        let gzipped_text = (compression == Gzipped || compression == Bgzf) && (file_fmt == Fasta || file_fmt == Fastq);
+        if !gzipped_text {
+            if threads > 1 {
+                return Err(DnaError::Unsupported(format!("{} cant be read on {} threads, only gzipped fasta/fastq can", filename, threads)));
+            }
+            return DnaReader::try_from_path(filename);
+        }
+        let source = parallel_gz_reader(filename, threads)?;
+        let reader: Box<DnaRead> = match file_fmt {
+            Fasta => {
+                let mut reader = FastaReader::from_read(source, Uncompressed);
+                reader.compression = compression;
+                Box::new(reader)
+            },
+            Fastq => {
+                let mut reader = FastqReader::from_read(source, Uncompressed);
+                reader.compression = compression;
+                Box::new(reader)
+            },
+            _ => unreachable!(),
         };
-        DnaReader{reader: reader}
+        Ok(DnaReader::new(reader))
+    }
+    // trusts the file contents over the filename, falls back on the extension when the bytes dont tell us.
+    // UnknownExtension when neither does
+    pub fn from_path_autodetect(filename: &str) -> Result<Self, DnaError> {
+        let mut buf_reader = BufReader::new(File::open(filename)?);
+        let sniffed = sniff_format(buf_reader.fill_buf()?);
+        let (file_fmt, compression) = match sniffed {
+            (Some(file_fmt), Some(compression)) => (file_fmt, compression),
+            (None, Some(compression)) => match sniff_compressed(filename, &compression).or_else(|| extension_format(filename).map(|(file_fmt, _)| file_fmt)) {
+                Some(file_fmt) => (file_fmt, compression),
+                None => return Err(DnaError::Parse(format!("file {} is compressed but I cant tell the format inside", filename))),
+            },
+            _ => match extension_format(filename) {
+                Some(x) => x,
+                None => return Err(DnaError::UnknownExtension(filename.to_string())),
+            },
+        };
+        Ok(match file_fmt {
+            Bam => DnaReader::new(Box::new(BamReader::new(filename)?)),
+            Cram => DnaReader::new(Box::new(CramReader::new(filename)?)),
+            TwoBit => DnaReader::new(Box::new(TwoBitReader::new(filename)?)),
+            _ => DnaReader::from_read(Box::new(buf_reader), file_fmt, compression),
+        })
+    }
+    // only cram uses the reference, everything else opens as usual
+    pub fn from_path_with_reference(filename: &str, ref_path: &str) -> Self {
+        match check_extension(filename) {
+            (Cram, _) => DnaReader::new(Box::new(CramReader::with_reference(filename, ref_path))),
+            _ => DnaReader::from_path(filename),
+        }
+    }
+    // for piping, eg zcat foo.fq.gz | mytool
+    pub fn from_stdin(format: DnaFormat, compression: Compression) -> Self {
+        match format {
+            Bam => DnaReader::new(Box::new(BamReader::from_stdin())),
+            _ => DnaReader::from_read(Box::new(std::io::stdin()), format, compression),
+        }
+    }
+    pub fn from_read(source: Box<std::io::Read>, format: DnaFormat, compression: Compression) -> Self {
+        let reader: Box<DnaRead> = match format {
+            Fasta => Box::new(FastaReader::from_read(source, compression)),
+            Fastq => Box::new(FastqReader::from_read(source, compression)),
+            Sam => Box::new(SamReader::from_read(source, compression)),
+            Raw => Box::new(RawReader::from_read(source, compression)),
+            Tsv => Box::new(TsvReader::from_read(source, compression)),
+            _ => panic!("format {:?} cannot be read from a stream",format),
+        };
+        DnaReader::new(reader)
+    }
+    // look at the next record without using it up, the following next() hands back the same one
+    pub fn peek(&mut self) -> Option<&DnaRecord> {
+        if self.peeked.is_empty() {
+            if let Some(rec) = self.next_checked() { self.peeked.push_back(rec); }
+        }
+        self.peeked.front()
+    }
+    // first two records look like mates, ie same stem with /1 then /2 or casava 1: then 2: comments.
+    // they get put back so iteration still starts at the first record
+    pub fn detect_interleaved(&mut self) -> bool {
+        while self.peeked.len() < 2 {
+            match self.next_checked() {
+                Some(rec) => self.peeked.push_back(rec),
+                None => return false,
+            }
+        }
+        let (first, second) = (&self.peeked[0].name, &self.peeked[1].name);
+        if pair_stem(first) != pair_stem(second) { return false; }
+        let id = |name: &str| name.split_whitespace().next().unwrap_or("").to_string();
+        let comment = |name: &str| name.split_whitespace().nth(1).unwrap_or("").to_string();
+        (id(first).ends_with("/1") && id(second).ends_with("/2")) ||
+            (comment(first).starts_with("1:") && comment(second).starts_with("2:"))
+    }
+    // restricts the following next() calls to a region, only indexed bam can do this
+    pub fn fetch(&mut self, region: &str) -> Result<(), DnaError> {
+        self.peeked.clear();
+        self.reader.fetch(region)
+    }
+    // same as counting next() but without making the strings, leaves the reader at the end
+    pub fn count_records(&mut self) -> u64 {
+        let peeked = self.peeked.len() as u64;
+        self.peeked.clear();
+        peeked + self.reader.count_records()
+    }
+    // up to the first n records, fewer if the file runs out. the reader carries on after them
+    pub fn head(&mut self, n: usize) -> Vec<DnaRecord> {
+        self.by_ref().take(n).collect()
+    }
+    // reads to the end of the file. keeps every length around for the n50 so 8 bytes a record
+    pub fn stats(&mut self) -> SeqStats {
+        let mut lengths: Vec<usize> = Vec::new();
+        let mut gc = 0;
+        let mut called = 0;
+        for rec in self.by_ref() {
+            for base in rec.seq.bytes() {
+                match base {
+                    b'G' | b'C' | b'g' | b'c' => { gc += 1; called += 1; },
+                    b'N' | b'n' => (),
+                    _ => called += 1,
+                }
+            }
+            lengths.push(rec.len());
+        }
+        if lengths.is_empty() { return SeqStats::default(); }
+        lengths.sort_unstable_by(|a, b| b.cmp(a));
+        let total: u64 = lengths.iter().map(|&len| len as u64).sum();
+        let mut running = 0;
+        let mut n50 = 0;
+        for &len in lengths.iter() {
+            running += len as u64;
+            if running * 2 >= total { n50 = len; break; }
+        }
+        SeqStats{
+            num_records: lengths.len() as u64,
+            total_length: total,
+            min_length: lengths[lengths.len() - 1],
+            max_length: lengths[0],
+            mean_length: total as f64 / lengths.len() as f64,
+            n50,
+            gc_percent: if called == 0 { 0.0 } else { 100.0 * gc as f64 / called as f64 },
+        }
+    }
+    // exactly min(n, total) reads picked uniformly in one pass without knowing the total, algorithm R.
+    // the same seed gives the same reads. holds n records in memory
+    pub fn reservoir_sample(&mut self, n: usize, seed: u64) -> Vec<DnaRecord> {
+        let mut reservoir = Vec::with_capacity(n);
+        let mut state = seed;
+        for (i, rec) in self.by_ref().enumerate() {
+            if i < n {
+                reservoir.push(rec);
+            } else {
+                let j = (splitmix64(&mut state) % (i as u64 + 1)) as usize;
+                if j < n { reservoir[j] = rec; }
+            }
+        }
+        reservoir
+    }
+    // keeps about fraction of the reads, the same ones every run for a given seed
+    pub fn subsample(self, fraction: f64, seed: u64) -> SubsampleReader {
+        SubsampleReader{ reader: self, fraction, seed }
+    }
+    // keep only the reads whose name_stem is in names, or only the ones that arent when keep is false.
+    // the set is moved in and lives as long as the reader, so how big it gets is up to the caller
+    pub fn filter_names(self, names: HashSet<String>, keep: bool) -> NameFilterReader {
+        NameFilterReader{ reader: self, names, keep }
+    }
+    // new name from the old one, eg reader.relabel(|name| format!("sampleA_{}", name)) before merging samples
+    pub fn relabel<F: FnMut(&str) -> String>(self, f: F) -> RelabelReader<F> {
+        RelabelReader{ reader: self, relabel: f }
+    }
+    // canonical k-mers of every record, see KmerIter. k of 1 to 32 so they fit in a u64
+    pub fn kmers(self, k: usize) -> KmerIter {
+        assert!(k > 0 && k <= 32, "k has to be between 1 and 32 to pack into a u64, got {}", k);
+        KmerIter{ reader: self, k, seq: Vec::new(), pos: 0, forward: 0, reverse: 0, valid: 0 }
+    }
+    // md5 of the sequences as they go by, eg to check two files have the same reads in the same order
+    pub fn with_digest(self) -> DigestReader {
+        DigestReader{ reader: self, md5: Md5::new() }
+    }
+    // drops low complexity reads, eg polyA/polyG. threshold is in bits, see shannon_entropy
+    pub fn min_entropy(self, threshold: f64) -> EntropyReader {
+        EntropyReader{ reader: self, threshold }
+    }
+    // only the first record with each name, eg secondary alignments out of a bam
+    pub fn dedup_by_name(self) -> DedupReader {
+        DedupReader{ reader: self, seen: HashSet::new() }
+    }
+    // skips anything shorter than n bases
+    pub fn min_length(self, n: usize) -> MinLengthReader {
+        MinLengthReader{ reader: self, min_length: n }
+    }
+    // panic on any record with something other than iupac bases, works for every format
+    pub fn with_validation(mut self) -> Self {
+        self.validate_alphabet = true;
+        self
+    }
+    fn next_checked(&mut self) -> Option<DnaRecord> {
+        let rec = self.reader.next()?;
+        if self.validate_alphabet { check_alphabet(&rec).unwrap_or_else(|err| panic!("{}", err)); }
+        Some(rec)
+    }
+    // like iterating but io/parse errors come out per record instead of panicking
+    pub fn records<'a>(&'a mut self) -> Records<'a> {
+        Records{ reader: self }
+    }
+    // start over from the first record, eg for a second pass. stdin and other streams cant do this
+    pub fn reset(&mut self) -> Result<(), DnaError> {
+        self.peeked.clear();
+        self.reader.reset()
     }
     pub fn header(&self) -> Option<bam::Header> { self.reader.header() }
     pub fn my_type(&self) -> DnaFormat { self.reader.my_type() }
     pub fn extension(&self) -> String { self.reader.extension() }
 }
 
+struct ChainedReader {
+    readers: Vec<Box<DnaRead>>,
+    current: usize,
+}
+
+impl DnaRead for ChainedReader {
+    fn next(&mut self) -> Option<DnaRecord> {
+        while self.current < self.readers.len() {
+            if let Some(rec) = self.readers[self.current].next() { return Some(rec); }
+            self.current += 1;
+        }
+        None
+    }
+    fn try_next(&mut self) -> Result<Option<DnaRecord>, DnaError> {
+        while self.current < self.readers.len() {
+            if let Some(rec) = self.readers[self.current].try_next()? { return Ok(Some(rec)); }
+            self.current += 1;
+        }
+        Ok(None)
+    }
+    fn count_records(&mut self) -> u64 {
+        let mut count = 0;
+        while self.current < self.readers.len() {
+            count += self.readers[self.current].count_records();
+            self.current += 1;
+        }
+        count
+    }
+    fn my_type(&self) -> DnaFormat { self.readers[0].my_type() }
+    fn header(&self) -> Option<bam::Header> { self.readers[0].header() }
+    fn extension(&self) -> String { self.readers[0].extension() }
+    fn reset(&mut self) -> Result<(), DnaError> {
+        for reader in self.readers.iter_mut() { reader.reset()?; }
+        self.current = 0;
+        Ok(())
+    }
+}
+
+// anything not set comes from the extension like from_path, buffer size only matters for fasta/fastq
+pub struct DnaReaderBuilder {
+    format: Option<DnaFormat>,
+    compression: Option<Compression>,
+    buffer_size: usize,
+    validate: bool,
+}
+
+impl DnaReaderBuilder {
+    pub fn new() -> Self {
+        DnaReaderBuilder{ format: None, compression: None, buffer_size: DEFAULT_BUFFER_SIZE, validate: false }
+    }
+    pub fn format(mut self, format: DnaFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+    // same as with_validation on the reader
+    pub fn validate(mut self, validate: bool) -> Self {
+        self.validate = validate;
+        self
+    }
+    pub fn build(&self, filename: &str) -> Result<DnaReader, DnaError> {
+        let (file_fmt, compression) = match (self.format.clone(), self.compression.clone()) {
+            (Some(file_fmt), Some(compression)) => (file_fmt, compression),
+            (file_fmt, compression) => match extension_format(filename) {
+                Some((ext_fmt, ext_compression)) => (file_fmt.unwrap_or(ext_fmt), compression.unwrap_or(ext_compression)),
+                None => return Err(DnaError::UnknownExtension(filename.to_string())),
+            },
+        };
+        let mut reader = match file_fmt {
+            Fasta => DnaReader::new(Box::new(FastaReader::with_buffer_size(filename, compression, self.buffer_size)?)),
+            Fastq => DnaReader::new(Box::new(FastqReader::with_buffer_size(filename, compression, self.buffer_size)?)),
+            _ => DnaReader::try_from_path_with_format(filename, file_fmt, compression)?,
+        };
+        reader.validate_alphabet = self.validate;
+        Ok(reader)
+    }
+}
+
+impl Default for DnaReaderBuilder {
+    fn default() -> Self { DnaReaderBuilder::new() }
+}
+
+// the writing side of DnaReaderBuilder, format always comes from the extension. level is 1 (fast) to 9 (small)
+// for compressed fasta/fastq and bam, header only matters for bam/sam/cram and is minimal_sam_header if not set
+pub struct DnaWriterBuilder {
+    compression: Option<Compression>,
+    level: Option<u32>,
+    header: Option<bam::Header>,
+}
+
+impl DnaWriterBuilder {
+    pub fn new() -> Self {
+        DnaWriterBuilder{ compression: None, level: None, header: None }
+    }
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = Some(level);
+        self
+    }
+    pub fn header(mut self, header: bam::Header) -> Self {
+        self.header = Some(header);
+        self
+    }
+    pub fn build(&self, filename: &str) -> Result<DnaWriter, DnaError> {
+        let (file_fmt, compression) = match extension_format(filename) {
+            Some((file_fmt, compression)) => (file_fmt, self.compression.clone().unwrap_or(compression)),
+            None => return Err(DnaError::UnknownExtension(filename.to_string())),
+        };
+        let header = self.header.clone().unwrap_or_else(minimal_sam_header);
+        let writer: Box<DnaWrite> = match file_fmt {
+            Fasta => Box::new(FastaWriter::with_level(filename, compression, self.level)),
+            Fastq => Box::new(FastqWriter::with_level(filename, compression, self.level)),
+            Raw => Box::new(RawWriter::with_level(filename, compression, self.level)),
+            Tsv => Box::new(TsvWriter::with_level(filename, compression, self.level)),
+            Bam => Box::new(BamWriter::with_level(filename, &header, self.level)),
+            Sam => Box::new(SamWriter::from_header(filename, &header)),
+            Cram => Box::new(CramWriter::from_header(filename, &header)),
+            TwoBit => return Err(DnaError::Unsupported(format!("writing {:?} is not implemented", file_fmt))),
+        };
+        Ok(DnaWriter{ writer })
+    }
+}
+
+impl Default for DnaWriterBuilder {
+    fn default() -> Self { DnaWriterBuilder::new() }
+}
+
 // for now we will assume that output is not gz'ed. they may want to stream to another program
 impl DnaWriter {
     pub fn from_reader(filename: &str, reader: &DnaReader) -> Self {
         let writer: Box<DnaWrite> = match reader.my_type() {
             Fastq => Box::new(FastqWriter::new(filename, Uncompressed)),
             Fasta => Box::new(FastaWriter::new(filename, Uncompressed)),
-            Sam | Bam | Cram => Box::new(SamWriter::new(filename, reader)),
+            Bam => Box::new(BamWriter::new(filename, reader, None)),
+            Sam => Box::new(SamWriter::new(filename, reader)),
+            Cram => Box::new(CramWriter::new(filename, reader)),
+            Raw => Box::new(RawWriter::new(filename, Uncompressed)),
+            Tsv => Box::new(TsvWriter::new(filename, Uncompressed)),
             TwoBit => panic!("unimplemented"),
         };
         DnaWriter{ writer: writer }
     }
+    // bam/sam with a header the caller put together, eg to add @PG or @RG lines
+    pub fn from_header(filename: &str, header: bam::Header) -> Self {
+        let writer: Box<DnaWrite> = match check_extension(filename) {
+            (Bam, _) => Box::new(BamWriter::from_header(filename, &header)),
+            (Sam, _) => Box::new(SamWriter::from_header(filename, &header)),
+            (Cram, _) => Box::new(CramWriter::from_header(filename, &header)),
+            (file_fmt, _) => panic!("format {:?} has no header, use from_path",file_fmt),
+        };
+        DnaWriter{ writer }
+    }
+    // bam written at compression level 1-9, sam/cram ignore the level
+    pub fn from_header_with_level(filename: &str, header: bam::Header, level: u32) -> Self {
+        match check_extension(filename) {
+            (Bam, _) => DnaWriter{ writer: Box::new(BamWriter::with_level(filename, &header, Some(level))) },
+            _ => DnaWriter::from_header(filename, header),
+        }
+    }
+    // format from the extension like from_path. the reader's own header wins, header is only used when it has none (fasta/fastq)
+    pub fn from_reader_with_header(filename: &str, reader: &DnaReader, header: bam::Header) -> Self {
+        let header = reader.header().unwrap_or(header);
+        match check_extension(filename) {
+            (Bam, _) | (Sam, _) | (Cram, _) => DnaWriter::from_header(filename, header),
+            _ => DnaWriter::from_path(filename),
+        }
+    }
+    // base_path plus whatever extension the reader has, eg out -> out.fastq.gz for a gzipped fastq
+    pub fn from_reader_auto_extension(base_path: &str, reader: &DnaReader) -> Self {
+        let filename = format!("{}{}", base_path, reader.extension());
+        match reader.my_type() {
+            Fasta | Fastq | Raw | Tsv => DnaWriter::from_path(&filename),
+            _ => DnaWriter::from_reader(&filename, reader),
+        }
+    }
     pub fn from_path(filename: &str) -> Self {
-        let (file_fmt, compression) = check_extension(filename);
+        let (_, compression) = check_extension(filename);
+        DnaWriter::from_path_with_compression(filename, compression)
+    }
+    // from_path without the panics, with create_dirs the parent directories are made first so out/sub/reads.fastq just works.
+    // sam/bam/cram need a header so theyre Unsupported here, use from_header
+    pub fn try_from_path(filename: &str, create_dirs: bool) -> Result<Self, DnaError> {
+        let (file_fmt, compression) = match extension_format(filename) {
+            Some(x) => x,
+            None => return Err(DnaError::UnknownExtension(filename.to_string())),
+        };
+        match file_fmt {
+            Fasta | Fastq | Raw | Tsv => (),
+            _ => return Err(DnaError::Unsupported(format!("writing {:?} without a header", file_fmt))),
+        }
+        if create_dirs {
+            if let Some(parent) = std::path::Path::new(filename).parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        let sink: Box<std::io::Write> = Box::new(File::create(filename)?);
+        let writer: Box<DnaWrite> = match file_fmt {
+            Fasta => Box::new(FastaWriter::from_write(sink, compression)),
+            Fastq => Box::new(FastqWriter::from_write(sink, compression)),
+            Raw => Box::new(RawWriter::from_write(sink, compression, None)),
+            Tsv => Box::new(TsvWriter::from_write(sink, compression, None)),
+            _ => unreachable!(),
+        };
+        Ok(DnaWriter{ writer })
+    }
+    // format still comes from the extension, eg Bgzf for a .fq.gz that needs to be indexable
+    pub fn from_path_with_compression(filename: &str, compression: Compression) -> Self {
+        let (file_fmt, _) = check_extension(filename);
+        DnaWriter::from_path_with_format(filename, file_fmt, compression)
+    }
+    // ignores the extension completely
+    pub fn from_path_with_format(filename: &str, file_fmt: DnaFormat, compression: Compression) -> Self {
         let writer: Box<DnaWrite> = match file_fmt {
             Fasta => Box::new(FastaWriter::new(filename, compression)),
             Fastq => Box::new(FastqWriter::new(filename, compression)),
+            Raw => Box::new(RawWriter::new(filename, compression)),
+            Tsv => Box::new(TsvWriter::new(filename, compression)),
             Sam => panic!("require from_reader for sam, I dont know how to make headers"),//Box::new(SamWriter(filename)),
             Bam => panic!("requires from_reader for bam writer, I dont know how to make headers"),//Box::new(BamWriter::new(filename, reader)),
             _ => panic!("file extension type {:?} not accepted.",file_fmt),
         };
         DnaWriter{ writer: writer }
     }
+    // 1 is fastest, 9 is smallest, only matters for compressed fasta/fastq and bam. bam gets just an @HD header
+    pub fn from_path_with_level(filename: &str, level: u32) -> Self {
+        let (file_fmt, compression) = check_extension(filename);
+        let writer: Box<DnaWrite> = match file_fmt {
+            Fasta => Box::new(FastaWriter::with_level(filename, compression, Some(level))),
+            Fastq => Box::new(FastqWriter::with_level(filename, compression, Some(level))),
+            Raw => Box::new(RawWriter::with_level(filename, compression, Some(level))),
+            Bam => Box::new(BamWriter::with_level(filename, &minimal_sam_header(), Some(level))),
+            Tsv => Box::new(TsvWriter::with_level(filename, compression, Some(level))),
+            _ => return DnaWriter::from_path_with_format(filename, file_fmt, compression),
+        };
+        DnaWriter{ writer }
+    }
+    // adds on to the end instead of truncating, compressed output just starts a new gzip member/frame
+    pub fn from_path_append(filename: &str) -> Self {
+        let (file_fmt, compression) = check_extension(filename);
+        let sink = append_file(filename);
+        let writer: Box<DnaWrite> = match file_fmt {
+            Fasta => Box::new(FastaWriter::from_write(sink, compression)),
+            Fastq => Box::new(FastqWriter::from_write(sink, compression)),
+            Raw => Box::new(RawWriter::from_write(sink, compression, None)),
+            Tsv => Box::new(TsvWriter::from_write(sink, compression, None)),
+            _ => panic!("format {:?} cannot be appended to",file_fmt),
+        };
+        DnaWriter{ writer }
+    }
+    pub fn to_stdout(format: DnaFormat, compression: Compression) -> Self {
+        let sink: Box<std::io::Write> = Box::new(StdoutSink{ stdout: std::io::stdout() });
+        let writer: Box<DnaWrite> = match format {
+            Fasta => Box::new(FastaWriter::from_write(sink, compression)),
+            Fastq => Box::new(FastqWriter::from_write(sink, compression)),
+            Raw => Box::new(RawWriter::from_write(sink, compression, None)),
+            Tsv => Box::new(TsvWriter::from_write(sink, compression, None)),
+            _ => panic!("format {:?} cannot be written to stdout",format),
+        };
+        DnaWriter{ writer }
+    }
     pub fn write(&mut self, rec: &DnaRecord) -> Result<(), Error> { self.writer.write(rec) }
+    // so far, bytes are uncompressed. bam/sam/cram go through htslib which doesnt tell us, so those are 0
+    pub fn records_written(&self) -> u64 { self.writer.records_written() }
+    pub fn bytes_written(&self) -> u64 { self.writer.bytes_written() }
+    // stops at the first error, eg writer.write_all(reader) to convert a whole file
+    pub fn write_all<I: IntoIterator<Item=DnaRecord>>(&mut self, records: I) -> Result<(), Error> {
+        for rec in records {
+            self.writer.write(&rec)?;
+        }
+        Ok(())
+    }
+    // also writes the gzip/zstd/bgzf trailer so the file is complete, writing more after starts a new member
+    pub fn flush(&mut self) -> Result<(), Error> { self.writer.flush() }
+    // flush and finish then let go of the writer, so errors writing the end of the file come back here instead of being lost in drop
+    pub fn close(mut self) -> Result<(), Error> { self.writer.flush() }
 }
-pub fn flush(writer: DnaWriter) {} // drop out of scope and flush/free automatically
+pub fn flush(mut writer: DnaWriter) -> Result<(), Error> { writer.flush() } // drop frees the rest
 
-impl Iterator for DnaReader {
-    type Item = DnaRecord;
-    fn next(&mut self) -> Option<DnaRecord> {
-        self.reader.next()
+// one out_dir/{id}.fasta per record, out_dir is made if it isnt there. a / in an id becomes _ so it
+// stays a file name. two records landing on the same file is an error rather than one overwriting the other
+pub fn split_fasta(input: &str, out_dir: &str) -> Result<Vec<String>, DnaError> {
+    let mut reader = DnaReader::try_from_path(input)?;
+    std::fs::create_dir_all(out_dir)?;
+    let mut paths = Vec::new();
+    let mut seen = HashSet::new();
+    for rec in reader.records() {
+        let rec = rec?;
+        let id = rec.name.replace("/", "_");
+        if !seen.insert(id.clone()) {
+            return Err(DnaError::Parse(format!("more than one record in {} would be written to {}.fasta", input, id)));
+        }
+        let path = std::path::Path::new(out_dir).join(format!("{}.fasta", id)).to_string_lossy().to_string();
+        let mut writer = FastaWriter::from_write(Box::new(File::create(&path)?), Uncompressed);
+        writer.write(&rec)?;
+        writer.flush()?;
+        paths.push(path);
     }
+    Ok(paths)
 }
 
-fn get_reader(filename: &str, compression: Compression) -> BufReader<Box<std::io::Read>> {
-	let file = File::open(filename).expect("There was a problem opening the file");
-    let reader: Box<std::io::Read> = match compression {
-      	Gzipped => Box::new(GzDecoder::new(file)),
-        Uncompressed => Box::new(file),
-    };
-    BufReader::new(reader)
+// format of each side comes from its own extension, returns how many records were written. none of the
+// writers keep alignments or tags, so a record that would lose any (eg aligned bam to fastq) is an
+// Unsupported error naming it. convert_lossy goes ahead anyway
+pub fn convert(input: &str, output: &str) -> Result<u64, DnaError> {
+    convert_records(input, output, false)
 }
 
-fn get_writer(filename: &str, compression: Compression) -> BufWriter<Box<std::io::Write>> {
-	let file = File::create(filename).expect("Unable to create file");
-    let writer: Box<std::io::Write> = match compression {
-        Gzipped => Box::new(GzEncoder::new(file, flate2::Compression::default())),
-        Uncompressed => Box::new(file),
-    };
-    BufWriter::new(writer)
+// convert but leaving behind whatever the output cant hold. going to fasta/fastq/raw secondary/supplementary
+// alignments are dropped so each read only shows up once, and reverse strand reads are flipped back to the
+// orientation they were sequenced in. flags, positions, mapq and tags are gone either way
+pub fn convert_lossy(input: &str, output: &str) -> Result<u64, DnaError> {
+    convert_records(input, output, true)
 }
 
-pub struct FastqReader {
-    pub buf_reader: BufReader<Box<std::io::Read>>,
-    compression: Compression,
+// what writing rec out would throw away, None when its only the name, bases and quals
+fn lost_in_conversion(rec: &DnaRecord) -> Option<String> {
+    let flags = rec.flags.unwrap_or(BAM_FUNMAP);
+    if flags & (BAM_FSECONDARY | BAM_FSUPPLEMENTARY) != 0 { return Some("is a secondary or supplementary alignment".to_string()); }
+    if flags & BAM_FREVERSE != 0 { return Some("is on the reverse strand".to_string()); }
+    if flags & BAM_FUNMAP == 0 { return Some("is aligned".to_string()); }
+    if flags != BAM_FUNMAP { return Some(format!("has flags {:#x}", flags)); }
+    if rec.tags.as_ref().is_some_and(|tags| !tags.is_empty()) { return Some("has aux tags".to_string()); }
+    None
 }
 
-pub struct FastqWriter {
-    pub buf_writer: BufWriter<Box<std::io::Write>>,
+fn convert_records(input: &str, output: &str, lossy: bool) -> Result<u64, DnaError> {
+    let mut reader = DnaReader::try_from_path(input)?;
+    let (out_fmt, _) = match extension_format(output) {
+        Some(x) => x,
+        None => return Err(DnaError::UnknownExtension(output.to_string())),
+    };
+    let header = reader.header().unwrap_or_else(minimal_sam_header);
+    let writer: Box<DnaWrite> = match out_fmt {
+        Bam => Box::new(BamWriter::try_with_level(output, &header, None)?),
+        Sam => Box::new(SamWriter::try_from_header(output, &header)?),
+        Cram => Box::new(CramWriter::try_from_header(output, &header)?),
+        TwoBit => return Err(DnaError::Unsupported(format!("writing {:?} is not implemented", out_fmt))),
+        _ => DnaWriter::try_from_path(output, false)?.writer,
+    };
+    let text_out = out_fmt != Bam && out_fmt != Sam && out_fmt != Cram;
+    let mut writer = DnaWriter{ writer };
+    let mut count = 0;
+    for rec in reader.records() {
+        let rec = rec?;
+        if !lossy {
+            if let Some(lost) = lost_in_conversion(&rec) {
+                return Err(DnaError::Unsupported(format!("record {} in {} {}, {:?} output cant keep that. use convert_lossy to drop it",
+                    rec.name, input, lost, out_fmt)));
+            }
+        }
+        if text_out && rec.flags.unwrap_or(0) & (BAM_FSECONDARY | BAM_FSUPPLEMENTARY) != 0 { continue; }
+        let rec = if text_out { unreverse(rec) } else { rec };
+        if out_fmt == Fastq && rec.qual.is_none() {
+            return Err(DnaError::Unsupported(format!("record {} in {} has no quality, cant write it to fastq", rec.name, input)));
+        }
+        writer.write(&rec)?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
 }
 
-impl FastqReader {
-    fn new(filename: &str, compression: Compression) -> Self {
-        FastqReader{ buf_reader: get_reader(filename, compression.clone()) , compression: compression}
+impl Iterator for DnaReader {
+    type Item = DnaRecord;
+    fn next(&mut self) -> Option<DnaRecord> {
+        match self.peeked.pop_front() {
+            Some(rec) => Some(rec),
+            None => self.next_checked(),
+        }
     }
 }
 
-impl FastqWriter {
-    fn new(filename: &str, compression: Compression) -> Self {
-        FastqWriter{ buf_writer: get_writer(filename, compression) }
-    }
+fn is_iupac(base: u8) -> bool {
+    matches!(base.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T' | b'U' | b'N' | b'R' | b'Y' | b'K' | b'M' | b'S' | b'W' | b'B' | b'D' | b'H' | b'V' | b'-')
 }
 
-impl DnaRead for FastqReader {
-    fn next(&mut self) -> Option<DnaRecord> {     
-        let mut name = String::new();
-        let mut seq = String::new();
-        let mut sep = String::new();
-        let mut qual = String::new();
-        match self.buf_reader.read_line(&mut name).expect("Could not read file") {0 => return None, _ => ()};
-        name.pop();
-        match self.buf_reader.read_line(&mut seq).expect("Could not read file") {0 => return None, _ => ()};
-        seq.pop();
-		match self.buf_reader.read_line(&mut sep).expect("Could not read file") {0 => return None, _ => ()};
-		match self.buf_reader.read_line(&mut qual).expect("Could not read file") {0 => return None, _ => ()};
-        qual.pop();
-		Some(DnaRecord{ name: name, seq: seq, qual: Some(qual)})        
+fn check_alphabet(rec: &DnaRecord) -> Result<(), DnaError> {
+    match rec.seq.bytes().find(|base| !is_iupac(*base)) {
+        Some(base) => Err(DnaError::Parse(format!("record {} has non-nucleotide character {:?} in its sequence", rec.name, base as char))),
+        None => Ok(()),
+    }
+}
+
+pub struct Records<'a> {
+    reader: &'a mut DnaReader,
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Result<DnaRecord, DnaError>;
+    fn next(&mut self) -> Option<Result<DnaRecord, DnaError>> {
+        if let Some(rec) = self.reader.peeked.pop_front() { return Some(Ok(rec)); }
+        match self.reader.reader.try_next() {
+            Ok(Some(rec)) => {
+                if self.reader.validate_alphabet {
+                    if let Err(err) = check_alphabet(&rec) { return Some(Err(err)); }
+                }
+                Some(Ok(rec))
+            },
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+pub struct SubsampleReader {
+    pub reader: DnaReader,
+    fraction: f64,
+    seed: u64,
+}
+
+impl Iterator for SubsampleReader {
+    type Item = DnaRecord;
+    fn next(&mut self) -> Option<DnaRecord> {
+        loop {
+            let rec = self.reader.next()?;
+            if subsample_draw(&rec.name, self.seed) < self.fraction { return Some(rec); }
+        }
+    }
+}
+
+pub struct MinLengthReader {
+    pub reader: DnaReader,
+    min_length: usize,
+}
+
+impl Iterator for MinLengthReader {
+    type Item = DnaRecord;
+    fn next(&mut self) -> Option<DnaRecord> {
+        loop {
+            let rec = self.reader.next()?;
+            if rec.len() >= self.min_length { return Some(rec); }
+        }
+    }
+}
+
+pub struct EntropyReader {
+    pub reader: DnaReader,
+    threshold: f64,
+}
+
+impl Iterator for EntropyReader {
+    type Item = DnaRecord;
+    fn next(&mut self) -> Option<DnaRecord> {
+        loop {
+            let rec = self.reader.next()?;
+            if rec.shannon_entropy() >= self.threshold { return Some(rec); }
+        }
+    }
+}
+
+// every distinct name is kept until the reader is dropped, so memory grows with the number of
+// reads, roughly the name length plus ~50 bytes each. a few GB for a 30M read file
+pub struct DedupReader {
+    pub reader: DnaReader,
+    seen: HashSet<String>,
+}
+
+impl Iterator for DedupReader {
+    type Item = DnaRecord;
+    fn next(&mut self) -> Option<DnaRecord> {
+        loop {
+            let rec = self.reader.next()?;
+            if self.seen.insert(rec.name.clone()) { return Some(rec); }
+        }
+    }
+}
+
+pub struct NameFilterReader {
+    pub reader: DnaReader,
+    names: HashSet<String>,
+    keep: bool,
+}
+
+impl Iterator for NameFilterReader {
+    type Item = DnaRecord;
+    fn next(&mut self) -> Option<DnaRecord> {
+        loop {
+            let rec = self.reader.next()?;
+            if self.names.contains(rec.name_stem()) == self.keep { return Some(rec); }
+        }
+    }
+}
+
+pub struct RelabelReader<F: FnMut(&str) -> String> {
+    pub reader: DnaReader,
+    relabel: F,
+}
+
+impl<F: FnMut(&str) -> String> Iterator for RelabelReader<F> {
+    type Item = DnaRecord;
+    fn next(&mut self) -> Option<DnaRecord> {
+        let mut rec = self.reader.next()?;
+        rec.name = (self.relabel)(&rec.name);
+        Some(rec)
+    }
+}
+
+// 2 bits a base, A=0 C=1 G=2 T=3 with the first base highest, and the smaller of the k-mer and its
+// reverse complement. windows with an N (or anything else that isnt ACGT) are skipped, k-mers dont
+// run across records. rolls both strands along one base at a time so nothing is allocated per k-mer
+pub struct KmerIter {
+    pub reader: DnaReader,
+    k: usize,
+    seq: Vec<u8>,
+    pos: usize,
+    forward: u64,
+    reverse: u64,
+    valid: usize, // ACGT bases in a row up to pos
+}
+
+impl Iterator for KmerIter {
+    type Item = u64;
+    fn next(&mut self) -> Option<u64> {
+        let mask = if self.k == 32 { !0u64 } else { (1u64 << (2 * self.k)) - 1 };
+        let shift = 2 * (self.k as u64 - 1);
+        loop {
+            if self.pos == self.seq.len() {
+                self.seq = self.reader.next()?.seq.into_bytes();
+                self.pos = 0;
+                self.valid = 0;
+                continue;
+            }
+            let code = match self.seq[self.pos] {
+                b'A' | b'a' => 0,
+                b'C' | b'c' => 1,
+                b'G' | b'g' => 2,
+                b'T' | b't' => 3,
+                _ => 4,
+            };
+            self.pos += 1;
+            if code == 4 {
+                self.valid = 0;
+                continue;
+            }
+            self.forward = ((self.forward << 2) | code) & mask;
+            self.reverse = (self.reverse >> 2) | ((3 - code) << shift);
+            self.valid += 1;
+            if self.valid >= self.k { return Some(std::cmp::min(self.forward, self.reverse)); }
+        }
+    }
+}
+
+// md5 of every sequence with a newline after it, in order. same as md5sum of the file a raw .txt writer would make
+pub struct DigestReader {
+    pub reader: DnaReader,
+    md5: Md5,
+}
+
+impl DigestReader {
+    // only covers the records read so far, the hex string md5sum prints
+    pub fn finalize_digest(&self) -> String {
+        self.md5.clone().finish().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+impl Iterator for DigestReader {
+    type Item = DnaRecord;
+    fn next(&mut self) -> Option<DnaRecord> {
+        let rec = self.reader.next()?;
+        self.md5.update(rec.seq.as_bytes());
+        self.md5.update(b"\n");
+        Some(rec)
+    }
+}
+
+// rfc 1321, streaming so the whole file never has to be in memory
+#[derive(Clone)]
+struct Md5 {
+    state: [u32; 4],
+    buffer: Vec<u8>,
+    length: u64,
+}
+
+const MD5_SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22,
+    5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20,
+    4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23,
+    6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21];
+
+// floor(abs(sin(i + 1)) * 2^32)
+const MD5_K: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391];
+
+impl Md5 {
+    fn new() -> Self {
+        Md5{ state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476], buffer: Vec::with_capacity(64), length: 0 }
+    }
+    fn update(&mut self, data: &[u8]) {
+        self.length += data.len() as u64;
+        for &byte in data {
+            self.buffer.push(byte);
+            if self.buffer.len() == 64 {
+                let block = std::mem::replace(&mut self.buffer, Vec::with_capacity(64));
+                self.compress(&block);
+            }
+        }
+    }
+    fn finish(mut self) -> [u8; 16] {
+        let bits = self.length.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.buffer.len() != 56 { self.update(&[0]); }
+        self.update(&bits.to_le_bytes());
+        let mut digest = [0u8; 16];
+        for (i, word) in self.state.iter().enumerate() {
+            for j in 0..4 { digest[4 * i + j] = (word >> (8 * j)) as u8; }
+        }
+        digest
+    }
+    fn compress(&mut self, block: &[u8]) {
+        let mut m = [0u32; 16];
+        for i in 0..16 {
+            m[i] = block[4 * i] as u32 | (block[4 * i + 1] as u32) << 8 | (block[4 * i + 2] as u32) << 16 | (block[4 * i + 3] as u32) << 24;
+        }
+        let (mut a, mut b, mut c, mut d) = (self.state[0], self.state[1], self.state[2], self.state[3]);
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a.wrapping_add(f).wrapping_add(MD5_K[i]).wrapping_add(m[g]).rotate_left(MD5_SHIFTS[i]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(rotated);
+        }
+        self.state[0] = self.state[0].wrapping_add(a);
+        self.state[1] = self.state[1].wrapping_add(b);
+        self.state[2] = self.state[2].wrapping_add(c);
+        self.state[3] = self.state[3].wrapping_add(d);
+    }
+}
+
+// the draw only depends on the seed and the read name minus its /1 /2, so subsampling R1 and R2
+// files with the same seed keeps the same pairs. fnv-1a of the name then splitmix64 to mix in the seed
+fn subsample_draw(name: &str, seed: u64) -> f64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in pair_stem(name).bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    let mut z = hash ^ seed.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+fn open_file(filename: &str) -> Result<Box<std::io::Read>, Error> {
+	let file = File::open(filename)?;
+    Ok(Box::new(file))
+}
+
+// the default 8k BufReader means a lot of tiny reads on big files
+pub const DEFAULT_BUFFER_SIZE: usize = 256 * 1024;
+
+// compressed streams cant seek, so going back to the start means opening the file again
+fn reopen_reader(path: &Option<String>, compression: &Compression, buffer_size: usize) -> Result<BufReader<Box<std::io::Read>>, DnaError> {
+    match *path {
+        Some(ref filename) => Ok(get_reader(open_file(filename)?, compression.clone(), buffer_size)),
+        None => Err(DnaError::Unsupported("cant go back to the start of a stream, only of a file".to_string())),
+    }
+}
+
+fn get_reader(source: Box<std::io::Read>, compression: Compression, buffer_size: usize) -> BufReader<Box<std::io::Read>> {
+    // decoders pull from the file in small pieces too, so buffer both sides
+    let source: Box<std::io::Read> = match compression {
+        Uncompressed => source,
+        _ => Box::new(BufReader::with_capacity(buffer_size, source)),
+    };
+    let reader: Box<std::io::Read> = match compression {
+        // multi member so concatenated files (and bgzf, which is lots of little members) read all the way through
+      	Gzipped | Bgzf => Box::new(MultiGzDecoder::new(source)),
+        Zstd => Box::new(zstd::stream::read::Decoder::new(source).expect("could not start zstd decoder")),
+        Bzip2 => Box::new(MultiBzDecoder::new(source)),
+        Uncompressed => source,
+    };
+    BufReader::with_capacity(buffer_size, reader)
+}
+
+fn parallel_gz_reader(filename: &str, threads: usize) -> Result<Box<std::io::Read>, DnaError> {
+    let mut file = BufReader::with_capacity(DEFAULT_BUFFER_SIZE, File::open(filename)?);
+    let is_bgzf = {
+        let start = file.fill_buf()?;
+        start.len() >= 14 && start[3] & 0x04 != 0 && start[12] == b'B' && start[13] == b'C'
+    };
+    if threads <= 1 {
+        Ok(Box::new(MultiGzDecoder::new(file)))
+    } else if is_bgzf {
+        let builder = gzp::par::decompress::ParDecompressBuilder::<gzp::deflate::Bgzf>::new().num_threads(threads)
+            .map_err(|err| Error::other(err.to_string()))?;
+        Ok(Box::new(builder.from_reader(file)))
+    } else {
+        Err(DnaError::Unsupported(format!("{} is plain gzip which cant be split over {} threads, bgzf can", filename, threads)))
+    }
+}
+
+fn create_file(filename: &str) -> Box<std::io::Write> {
+	let file = File::create(filename).expect("Unable to create file");
+    Box::new(file)
+}
+
+// level None leaves each codec on its own default, otherwise 1 (fast) to 9 (small) for gzip/bgzf/bzip2
+// and handed straight to zstd which goes up to 22
+fn get_writer(sink: Box<std::io::Write>, compression: Compression, level: Option<u32>) -> BufWriter<Box<FinishWrite>> {
+    let writer: Box<FinishWrite> = match compression {
+        Gzipped => Box::new(gz_sink(sink, level)),
+        Bgzf => Box::new(BgzfWriter::new(sink, level)),
+        Zstd => Box::new(zstd_sink(sink, level)),
+        Bzip2 => Box::new(bzip2_sink(sink, level)),
+        Uncompressed => Box::new(sink),
+    };
+    BufWriter::new(writer)
+}
+
+fn append_file(filename: &str) -> Box<std::io::Write> {
+    let file = std::fs::OpenOptions::new().append(true).create(true).open(filename).expect("Unable to open file for appending");
+    Box::new(file)
+}
+
+// the layer under the fastq/fasta BufWriter. finish writes whatever trailer the compression needs,
+// anything written after that goes into a fresh gzip member/zstd frame which readers just concatenate
+pub trait FinishWrite: std::io::Write {
+    fn finish(&mut self) -> std::io::Result<()>;
+}
+
+impl FinishWrite for Box<std::io::Write> {
+    fn finish(&mut self) -> std::io::Result<()> { self.flush() }
+}
+
+// the encoders only write their trailer on finish (or silently on drop) and finish eats the encoder,
+// so hold on to the sink between members and start a fresh encoder on the next write
+struct MemberSink<E: std::io::Write> {
+    inner: Option<Box<std::io::Write>>,
+    encoder: Option<E>,
+    level: Option<u32>,
+    start: fn(Box<std::io::Write>, Option<u32>) -> std::io::Result<E>,
+    end: fn(E) -> std::io::Result<Box<std::io::Write>>,
+}
+
+impl<E: std::io::Write> MemberSink<E> {
+    fn new(inner: Box<std::io::Write>, level: Option<u32>, start: fn(Box<std::io::Write>, Option<u32>) -> std::io::Result<E>,
+            end: fn(E) -> std::io::Result<Box<std::io::Write>>) -> std::io::Result<Self> {
+        Ok(MemberSink{ inner: None, encoder: Some(start(inner, level)?), level, start, end })
+    }
+}
+
+impl<E: std::io::Write> std::io::Write for MemberSink<E> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.encoder.is_none() {
+            match self.inner.take() {
+                Some(inner) => self.encoder = Some((self.start)(inner, self.level)?),
+                None => return Err(Error::other("compressed output lost after an earlier error")),
+            }
+        }
+        self.encoder.as_mut().unwrap().write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        match (self.encoder.as_mut(), self.inner.as_mut()) {
+            (Some(encoder), _) => encoder.flush(),
+            (None, Some(inner)) => inner.flush(),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+impl<E: std::io::Write> FinishWrite for MemberSink<E> {
+    fn finish(&mut self) -> std::io::Result<()> {
+        if let Some(encoder) = self.encoder.take() {
+            let mut inner = (self.end)(encoder)?;
+            inner.flush()?;
+            self.inner = Some(inner);
+        }
+        Ok(())
+    }
+}
+
+impl<E: std::io::Write> Drop for MemberSink<E> {
+    fn drop(&mut self) { let _ = self.finish(); }
+}
+
+fn flate2_level(level: Option<u32>) -> flate2::Compression {
+    match level {
+        Some(level) => flate2::Compression::new(std::cmp::min(level, 9)),
+        None => flate2::Compression::default(),
+    }
+}
+
+fn gz_sink(sink: Box<std::io::Write>, level: Option<u32>) -> MemberSink<GzEncoder<Box<std::io::Write>>> {
+    MemberSink::new(sink, level, |w, level| Ok(GzEncoder::new(w, flate2_level(level))), |e| e.finish())
+        .expect("could not start gzip encoder")
+}
+
+fn zstd_sink(sink: Box<std::io::Write>, level: Option<u32>) -> MemberSink<zstd::stream::write::Encoder<Box<std::io::Write>>> {
+    MemberSink::new(sink, level, |w, level| zstd::stream::write::Encoder::new(w, level.unwrap_or(0) as i32), |e| e.finish())
+        .expect("could not start zstd encoder")
+}
+
+fn bzip2_sink(sink: Box<std::io::Write>, level: Option<u32>) -> MemberSink<BzEncoder<Box<std::io::Write>>> {
+    let start = |w, level: Option<u32>| {
+        let level = match level {
+            Some(level) => bzip2::Compression::new(level.clamp(1, 9)),
+            None => bzip2::Compression::default(),
+        };
+        Ok(BzEncoder::new(w, level))
+    };
+    MemberSink::new(sink, level, start, |e| e.finish()).expect("could not start bzip2 encoder")
+}
+
+// bgzf is a series of gzip members holding at most 64k each, with the member size stashed in a BC
+// extra field so htslib/tabix can jump between blocks. ends with an empty block as the EOF marker
+const BGZF_BLOCK_SIZE: usize = 0xff00;
+const BGZF_EOF: [u8; 28] = [0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43,
+    0x02, 0x00, 0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+
+struct BgzfWriter {
+    inner: Box<std::io::Write>,
+    buffer: Vec<u8>,
+    eof_written: bool,
+    level: flate2::Compression,
+    written: u64, // compressed bytes out so far, where the block being filled will start
+}
+
+impl BgzfWriter {
+    fn new(inner: Box<std::io::Write>, level: Option<u32>) -> Self {
+        BgzfWriter{ inner, buffer: Vec::with_capacity(BGZF_BLOCK_SIZE), eof_written: false, level: flate2_level(level), written: 0 }
+    }
+    // block start in the compressed file in the top 48 bits, position inside the block in the low 16
+    fn virtual_offset(&self) -> u64 { (self.written << 16) | self.buffer.len() as u64 }
+    fn write_block(&mut self) -> std::io::Result<()> {
+        if self.buffer.is_empty() { return Ok(()); }
+        let mut encoder = DeflateEncoder::new(Vec::new(), self.level);
+        encoder.write_all(&self.buffer)?;
+        let compressed = encoder.finish()?;
+        let mut crc = flate2::Crc::new();
+        crc.update(&self.buffer);
+        let block_size = compressed.len() + 26 - 1; // 18 byte header and 8 byte trailer, stored minus one
+        let header = [0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00,
+            (block_size & 0xff) as u8, (block_size >> 8) as u8];
+        self.inner.write_all(&header)?;
+        self.inner.write_all(&compressed)?;
+        self.inner.write_all(&crc.sum().to_le_bytes())?;
+        self.inner.write_all(&(self.buffer.len() as u32).to_le_bytes())?;
+        self.written += block_size as u64 + 1;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl std::io::Write for BgzfWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = std::cmp::min(buf.len(), BGZF_BLOCK_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..n]);
+        if n > 0 { self.eof_written = false; }
+        if self.buffer.len() == BGZF_BLOCK_SIZE { self.write_block()?; }
+        Ok(n)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.write_block()?;
+        self.inner.flush()
+    }
+}
+
+// an empty block mid file is harmless, so finishing more than once is fine
+impl FinishWrite for BgzfWriter {
+    fn finish(&mut self) -> std::io::Result<()> {
+        if self.eof_written { return Ok(()); }
+        self.write_block()?;
+        self.inner.write_all(&BGZF_EOF)?;
+        self.inner.flush()?;
+        self.written += BGZF_EOF.len() as u64;
+        self.eof_written = true;
+        Ok(())
+    }
+}
+
+impl Drop for BgzfWriter {
+    fn drop(&mut self) { let _ = self.finish(); }
+}
+
+// reads bgzf a block at a time so the virtual offset of wherever we are is always known.
+// rust_htslib 0.24 has no safe api for bgzf files, so this unpacks the blocks itself
+struct BgzfBlockReader {
+    file: BufReader<File>,
+    block: Vec<u8>,
+    pos: usize,
+    block_start: u64,
+    next_block_start: u64,
+}
+
+impl BgzfBlockReader {
+    fn new(filename: &str) -> Result<Self, Error> {
+        Ok(BgzfBlockReader{ file: BufReader::new(File::open(filename)?), block: Vec::new(), pos: 0, block_start: 0, next_block_start: 0 })
+    }
+    fn virtual_offset(&self) -> u64 { (self.block_start << 16) | self.pos as u64 }
+    fn seek(&mut self, virtual_offset: u64) -> Result<(), Error> {
+        self.next_block_start = virtual_offset >> 16;
+        self.file.seek(std::io::SeekFrom::Start(self.next_block_start))?;
+        self.read_block()?;
+        let pos = (virtual_offset & 0xffff) as usize;
+        if pos > self.block.len() {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("virtual offset {} is past the end of its block", virtual_offset)));
+        }
+        self.pos = pos;
+        Ok(())
+    }
+    // false at the end of the file
+    fn read_block(&mut self) -> Result<bool, Error> {
+        self.block_start = self.next_block_start;
+        self.block.clear();
+        self.pos = 0;
+        let mut header = [0u8; 12];
+        if self.file.read(&mut header[..1])? == 0 { return Ok(false); }
+        self.file.read_exact(&mut header[1..])?;
+        if header[..4] != [0x1f, 0x8b, 0x08, 0x04] {
+            return Err(Error::new(ErrorKind::InvalidData, "not a bgzf block, is this file bgzipped?"));
+        }
+        let mut extra = vec![0u8; header[10] as usize | (header[11] as usize) << 8];
+        self.file.read_exact(&mut extra)?;
+        // BC subfield holds the whole block size minus one
+        let mut block_size = None;
+        let mut i = 0;
+        while i + 4 <= extra.len() {
+            let len = extra[i + 2] as usize | (extra[i + 3] as usize) << 8;
+            if extra[i] == b'B' && extra[i + 1] == b'C' && len == 2 && i + 6 <= extra.len() {
+                block_size = Some((extra[i + 4] as usize | (extra[i + 5] as usize) << 8) + 1);
+            }
+            i += 4 + len;
+        }
+        let block_size = match block_size {
+            Some(x) => x,
+            None => return Err(Error::new(ErrorKind::InvalidData, "gzip block has no BC field, is this file bgzipped?")),
+        };
+        if block_size < 12 + extra.len() + 8 {
+            return Err(Error::new(ErrorKind::InvalidData, "bgzf block size is too small"));
+        }
+        let mut compressed = vec![0u8; block_size - 12 - extra.len() - 8];
+        self.file.read_exact(&mut compressed)?;
+        let mut trailer = [0u8; 8];
+        self.file.read_exact(&mut trailer)?;
+        DeflateDecoder::new(&compressed[..]).read_to_end(&mut self.block)?;
+        self.next_block_start = self.block_start + block_size as u64;
+        Ok(true)
+    }
+}
+
+impl std::io::Read for BgzfBlockReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = {
+            let available = self.fill_buf()?;
+            let n = std::cmp::min(available.len(), buf.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for BgzfBlockReader {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        // loop since the eof marker and flushes leave empty blocks around
+        while self.pos == self.block.len() {
+            if !self.read_block()? { break; }
+        }
+        Ok(&self.block[self.pos..])
+    }
+    fn consume(&mut self, amt: usize) { self.pos += amt; }
+}
+
+// bgzipped fastq that can jump to a virtual offset, eg one taken from virtual_offset() or
+// BgzfFastqWriter::last_offset() while building an index
+pub struct BgzfFastqReader {
+    reader: BgzfBlockReader,
+}
+
+impl BgzfFastqReader {
+    pub fn new(filename: &str) -> Result<Self, Error> {
+        Ok(BgzfFastqReader{ reader: BgzfBlockReader::new(filename)? })
+    }
+    pub fn seek(&mut self, virtual_offset: u64) -> Result<(), Error> { self.reader.seek(virtual_offset) }
+    // where the next record starts
+    pub fn virtual_offset(&self) -> u64 { self.reader.virtual_offset() }
+}
+
+impl DnaRead for BgzfFastqReader {
+    fn next(&mut self) -> Option<DnaRecord> {
+        match self.try_next() {
+            Ok(rec) => rec,
+            Err(err) => panic!("{}", err),
+        }
+    }
+    fn try_next(&mut self) -> Result<Option<DnaRecord>, DnaError> {
+        Ok(read_fastq_record(&mut self.reader, false)?.map(|(rec, _)| rec))
+    }
+    fn my_type(&self) -> DnaFormat { Fastq }
+    fn header(&self) -> Option<bam::Header> { None }
+    fn extension(&self) -> String { ".fastq.gz".to_string() }
+    fn reset(&mut self) -> Result<(), DnaError> {
+        self.reader.seek(0)?;
+        Ok(())
+    }
+}
+
+// bgzf fastq where a block only ever ends between records (unless one record is bigger than a block),
+// so every record can be got at by seeking straight to its start
+pub struct BgzfFastqWriter {
+    writer: BgzfWriter,
+    last_offset: Option<u64>,
+    records: u64,
+    bytes: u64,
+}
+
+impl BgzfFastqWriter {
+    pub fn new(filename: &str) -> Self {
+        BgzfFastqWriter{ writer: BgzfWriter::new(create_file(filename), None), last_offset: None, records: 0, bytes: 0 }
+    }
+    // virtual offset the last record written starts at, None before any are written
+    pub fn last_offset(&self) -> Option<u64> { self.last_offset }
+}
+
+impl DnaWrite for BgzfFastqWriter {
+    fn write(&mut self, rec: &DnaRecord) -> Result<(), Error> {
+        let to_write = match rec.to_fastq_string() {
+            Ok(x) => x,
+            Err(_) => panic!("I have no qual i cant write fastq"),
+        };
+        if !self.writer.buffer.is_empty() && self.writer.buffer.len() + to_write.len() > BGZF_BLOCK_SIZE {
+            self.writer.write_block()?;
+        }
+        self.last_offset = Some(self.writer.virtual_offset());
+        self.writer.write_all(to_write.as_bytes())?;
+        self.records += 1;
+        self.bytes += to_write.len() as u64;
+        Ok(())
+    }
+    fn flush(&mut self) -> Result<(), Error> { self.writer.finish() }
+    fn records_written(&self) -> u64 { self.records }
+    fn bytes_written(&self) -> u64 { self.bytes }
+}
+
+// stdout is line buffered and a gzip trailer has no newline, so push it out when we are done
+struct StdoutSink {
+    stdout: std::io::Stdout,
+}
+
+impl std::io::Write for StdoutSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> { self.stdout.write(buf) }
+    fn flush(&mut self) -> std::io::Result<()> { self.stdout.flush() }
+}
+
+impl Drop for StdoutSink {
+    fn drop(&mut self) { let _ = self.stdout.flush(); }
+}
+
+pub struct FastqReader {
+    pub buf_reader: BufReader<Box<std::io::Read>>,
+    compression: Compression,
+    lookahead: VecDeque<(u64, DnaRecord)>, // records already parsed by guess_encoding but not handed out yet, with their offsets
+    pub validate_alphabet: bool, // panic on anything that isnt an iupac base
+    pub strict: bool, // anything after the + has to be the same as the header line
+    path: Option<String>, // None when reading from a stream
+    offset: u64, // decompressed bytes parsed so far
+}
+
+pub struct FastqWriter {
+    pub buf_writer: BufWriter<Box<FinishWrite>>,
+    pub default_quality: Option<u8>, // phred score given to every base of records with no qual, eg from fasta
+    pub member_per_record: bool, // finish the gzip member/zstd frame after every record so each one can be seeked to on its own
+    records: u64,
+    bytes: u64,
+}
+
+impl FastqReader {
+    pub fn new(filename: &str, compression: Compression) -> Result<Self, Error> {
+        FastqReader::with_buffer_size(filename, compression, DEFAULT_BUFFER_SIZE)
+    }
+    pub fn with_buffer_size(filename: &str, compression: Compression, buffer_size: usize) -> Result<Self, Error> {
+        let mut reader = FastqReader::from_read_with_buffer_size(open_file(filename)?, compression, buffer_size);
+        reader.path = Some(filename.to_string());
+        Ok(reader)
+    }
+    fn from_read(source: Box<std::io::Read>, compression: Compression) -> Self {
+        FastqReader::from_read_with_buffer_size(source, compression, DEFAULT_BUFFER_SIZE)
+    }
+    fn from_read_with_buffer_size(source: Box<std::io::Read>, compression: Compression, buffer_size: usize) -> Self {
+        FastqReader{ buf_reader: get_reader(source, compression.clone(), buffer_size) , compression, lookahead: VecDeque::new(),
+            validate_alphabet: false, strict: false, path: None, offset: 0 }
+    }
+    // phred+64 starts at '@' (64) so anything below ';' (59) can only be phred+33. the records
+    // scanned are kept and still come out of next()
+    pub fn guess_encoding(&mut self) -> u8 {
+        while self.lookahead.len() < GUESS_ENCODING_RECORDS {
+            let offset = self.offset;
+            match self.read_record() {
+                Some(rec) => self.lookahead.push_back((offset, rec)),
+                None => break,
+            }
+        }
+        for (_, rec) in self.lookahead.iter() {
+            if let Some(ref qual) = rec.qual {
+                if qual.bytes().any(|q| q < 59) { return 33; }
+            }
+        }
+        if self.lookahead.is_empty() { 33 } else { 64 }
+    }
+    // where the record the next call to next() gives back starts, in decompressed bytes.
+    // for an uncompressed file this is the file offset, eg for building an index
+    pub fn current_offset(&self) -> u64 {
+        match self.lookahead.front() {
+            Some(&(offset, _)) => offset,
+            None => self.offset,
+        }
+    }
+    pub fn next_with_offset(&mut self) -> Option<(u64, DnaRecord)> {
+        let offset = self.current_offset();
+        self.next().map(|rec| (offset, rec))
+    }
+    fn read_record(&mut self) -> Option<DnaRecord> {
+        match self.try_read_record() {
+            Ok(rec) => rec,
+            Err(err) => panic!("{}", err),
+        }
+    }
+    // Ok(None) only at a clean end of file, running out partway through a record is an error
+    fn try_read_record(&mut self) -> Result<Option<DnaRecord>, DnaError> {
+        match read_fastq_record(&mut self.buf_reader, self.strict)? {
+            Some((rec, bytes)) => {
+                self.offset += bytes as u64;
+                Ok(Some(rec))
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+// the record and how many bytes it took up
+fn read_fastq_record<R: BufRead>(reader: &mut R, strict: bool) -> Result<Option<(DnaRecord, usize)>, DnaError> {
+    let mut name = String::new();
+    let mut seq = String::new();
+    let mut sep = String::new();
+    let mut qual = String::new();
+    // blank lines where a name should be, eg trailing ones at the end of the file, arent a record
+    let mut bytes = 0;
+    loop {
+        name.clear();
+        bytes += match reader.read_line(&mut name)? {0 => return Ok(None), x => x};
+        if !name.trim().is_empty() { break; }
+    }
+    chomp(&mut name);
+    if name.starts_with('@') { name.remove(0); }
+    let truncated = || DnaError::Parse(format!("fastq record {} is truncated", name));
+    // sequence can be wrapped over several lines, runs until the + line
+    loop {
+        sep.clear();
+        bytes += match reader.read_line(&mut sep)? {0 => return Err(truncated()), x => x};
+        if sep.starts_with('+') { break; }
+        chomp(&mut sep);
+        seq.push_str(&sep);
+    }
+    chomp(&mut sep);
+    if strict && sep.len() > 1 && sep[1..] != name[..] {
+        return Err(DnaError::Parse(format!("fastq record {} has separator line {} that doesnt match its name", name, sep)));
+    }
+    // quality lines can start with @ so go by length instead, always at least one line for empty reads
+    let mut line = String::new();
+    loop {
+        line.clear();
+        bytes += match reader.read_line(&mut line)? {0 => return Err(truncated()), x => x};
+        chomp(&mut line);
+        qual.push_str(&line);
+        if qual.len() >= seq.len() { break; }
+    }
+	Ok(Some((DnaRecord{ name, seq, qual: Some(qual), ..Default::default() }, bytes)))
+}
+
+// the same walk as read_fastq_record but only adding up lengths in one reused buffer, for count_records
+fn skip_fastq_record<R: BufRead>(reader: &mut R, line: &mut Vec<u8>) -> Result<bool, DnaError> {
+    let content_len = |line: &[u8]| line.iter().rposition(|&c| c != b'\n' && c != b'\r').map_or(0, |i| i + 1);
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', line)? == 0 { return Ok(false); }
+        if line.iter().any(|c| !c.is_ascii_whitespace()) { break; }
+    }
+    let truncated = |line: &[u8]| DnaError::Parse(format!("fastq record {} is truncated", String::from_utf8_lossy(line).trim()));
+    let name = line.clone();
+    let mut seq_len = 0;
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', line)? == 0 { return Err(truncated(&name)); }
+        if line.starts_with(b"+") { break; }
+        seq_len += content_len(line);
+    }
+    let mut qual_len = 0;
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', line)? == 0 { return Err(truncated(&name)); }
+        qual_len += content_len(line);
+        if qual_len >= seq_len { break; }
+    }
+    Ok(true)
+}
+
+const GUESS_ENCODING_RECORDS: usize = 1000;
+
+impl FastqWriter {
+    fn new(filename: &str, compression: Compression) -> Self {
+        FastqWriter::with_level(filename, compression, None)
+    }
+    pub fn with_level(filename: &str, compression: Compression, level: Option<u32>) -> Self {
+        FastqWriter::from_write_with_level(create_file(filename), compression, level)
+    }
+    fn from_write(sink: Box<std::io::Write>, compression: Compression) -> Self {
+        FastqWriter::from_write_with_level(sink, compression, None)
+    }
+    fn from_write_with_level(sink: Box<std::io::Write>, compression: Compression, level: Option<u32>) -> Self {
+        FastqWriter{ buf_writer: get_writer(sink, compression, level), default_quality: None, member_per_record: false, records: 0, bytes: 0 }
+    }
+}
+
+impl DnaRead for FastqReader {
+    fn next(&mut self) -> Option<DnaRecord> {
+        match self.try_next() {
+            Ok(rec) => rec,
+            Err(err) => panic!("{}", err),
+        }
+    }
+    fn try_next(&mut self) -> Result<Option<DnaRecord>, DnaError> {
+        let rec = match self.lookahead.pop_front() {
+            Some((_, rec)) => rec,
+            None => match self.try_read_record()? {
+                Some(rec) => rec,
+                None => return Ok(None),
+            },
+        };
+        if self.validate_alphabet { check_alphabet(&rec)?; }
+        Ok(Some(rec))
+    }
+    // wrapped records mean the lines cant just be divided by 4, so this follows the records by length
+    // without building them
+    fn count_records(&mut self) -> u64 {
+        let mut count = self.lookahead.len() as u64;
+        self.lookahead.clear();
+        let mut line = Vec::new();
+        while skip_fastq_record(&mut self.buf_reader, &mut line).unwrap_or_else(|err| panic!("{}", err)) { count += 1; }
+        count
     }
 	fn my_type(&self) -> DnaFormat {
 		Fastq
@@ -193,54 +2114,416 @@ impl DnaRead for FastqReader {
     }
     fn extension(&self) -> String {
         let mut to_ret = ".fastq".to_string();
-        if self.compression == Gzipped { to_ret.push_str(".gz") }
+        to_ret.push_str(compression_extension(&self.compression));
         to_ret
     }
+    fn reset(&mut self) -> Result<(), DnaError> {
+        self.buf_reader = reopen_reader(&self.path, &self.compression, self.buf_reader.capacity())?;
+        self.lookahead.clear();
+        self.offset = 0;
+        Ok(())
+    }
 }
 
 impl DnaWrite for FastqWriter {
     fn write(&mut self, rec: &DnaRecord) -> Result<(), Error> {
-        let qual = match rec.qual {
-            Some(ref x) => x,
-            None => panic!("I have no qual i cant write fastq"),
+        let to_write = match (rec.to_fastq_string(), self.default_quality) {
+            (Ok(x), _) => x,
+            (Err(_), Some(phred)) => {
+                let qual = (phred.saturating_add(33) as char).to_string().repeat(rec.seq.len());
+                rec.with_seq_qual(rec.seq.clone(), Some(qual)).to_fastq_string().expect("just gave it a qual")
+            },
+            (Err(_), None) => panic!("I have no qual i cant write fastq"),
         };
-        let to_write = format!("{}\n{}\n+\n{}\n",rec.name,rec.seq,qual);
-        self.buf_writer.write_all(&to_write.as_bytes())
+        self.buf_writer.write_all(to_write.as_bytes())?;
+        self.records += 1;
+        self.bytes += to_write.len() as u64;
+        // compresses a lot worse, a gzip header and trailer on every read
+        if self.member_per_record { self.flush()?; }
+        Ok(())
+    }
+    fn flush(&mut self) -> Result<(), Error> {
+        self.buf_writer.flush()?;
+        self.buf_writer.get_mut().finish()
+    }
+    fn records_written(&self) -> u64 { self.records }
+    fn bytes_written(&self) -> u64 { self.bytes }
+}
+
+pub struct PairedFastqReader {
+    pub r1: FastqReader,
+    pub r2: FastqReader,
+}
+
+impl PairedFastqReader {
+    pub fn new(r1: &str, r2: &str) -> Self {
+        PairedFastqReader{ r1: open_fastq(r1), r2: open_fastq(r2) }
     }
 }
 
+fn open_fastq(filename: &str) -> FastqReader {
+    let compression = match check_extension(filename) {
+        (Fastq, compression) => compression,
+        (file_fmt, _) => panic!("paired reading needs fastq, {} looks like {:?}",filename,file_fmt),
+    };
+    match FastqReader::new(filename, compression) {
+        Ok(reader) => reader,
+        Err(err) => panic!("could not open {}: {}",filename,err),
+    }
+}
+
+// read name up to the first space with any /1 or /2 taken off, which is what mates have in common
+fn pair_stem(name: &str) -> &str {
+    let name = name.split_whitespace().next().unwrap_or("");
+    if name.ends_with("/1") || name.ends_with("/2") { &name[..name.len()-2] } else { name }
+}
+
+impl Iterator for PairedFastqReader {
+    type Item = (DnaRecord, DnaRecord);
+    fn next(&mut self) -> Option<(DnaRecord, DnaRecord)> {
+        match (self.r1.next(), self.r2.next()) {
+            (None, None) => None,
+            (Some(rec1), Some(rec2)) => {
+                assert!(pair_stem(&rec1.name) == pair_stem(&rec2.name), "paired reads out of sync, {} does not match {}", rec1.name, rec2.name);
+                Some((rec1, rec2))
+            },
+            _ => panic!("paired fastq files have different numbers of records"),
+        }
+    }
+}
+
+pub struct InterleavedFastqWriter {
+    pub writer: FastqWriter,
+}
+
+impl InterleavedFastqWriter {
+    pub fn new(filename: &str, compression: Compression) -> Self {
+        InterleavedFastqWriter{ writer: FastqWriter::new(filename, compression) }
+    }
+    pub fn write_pair(&mut self, r1: &DnaRecord, r2: &DnaRecord) -> Result<(), Error> {
+        self.writer.write(&with_mate_suffix(r1, "/1"))?;
+        self.writer.write(&with_mate_suffix(r2, "/2"))
+    }
+    pub fn flush(&mut self) -> Result<(), Error> { self.writer.flush() }
+}
+
+// out -> out_0.fastq, out_1.fastq, ... with records_per_chunk reads in each, a file is only made once theres a record for it
+pub struct ChunkedFastqWriter {
+    base_path: String,
+    records_per_chunk: usize,
+    chunk: usize,
+    in_chunk: usize,
+    writer: Option<FastqWriter>,
+    records: u64, // totals from the chunks already closed
+    bytes: u64,
+}
+
+impl ChunkedFastqWriter {
+    pub fn new(base_path: &str, records_per_chunk: usize) -> Self {
+        assert!(records_per_chunk > 0, "chunks need at least one record each");
+        ChunkedFastqWriter{ base_path: base_path.to_string(), records_per_chunk, chunk: 0, in_chunk: 0, writer: None, records: 0, bytes: 0 }
+    }
+    // how many files have been started so far
+    pub fn chunks(&self) -> usize { self.chunk }
+}
+
+impl DnaWrite for ChunkedFastqWriter {
+    fn write(&mut self, rec: &DnaRecord) -> Result<(), Error> {
+        if self.writer.is_none() || self.in_chunk == self.records_per_chunk {
+            if let Some(ref mut writer) = self.writer {
+                writer.flush()?;
+                self.records += writer.records_written();
+                self.bytes += writer.bytes_written();
+            }
+            let filename = format!("{}_{}.fastq", self.base_path, self.chunk);
+            self.writer = Some(FastqWriter::new(&filename, Uncompressed));
+            self.chunk += 1;
+            self.in_chunk = 0;
+        }
+        self.in_chunk += 1;
+        match self.writer {
+            Some(ref mut writer) => writer.write(rec),
+            None => unreachable!(),
+        }
+    }
+    fn flush(&mut self) -> Result<(), Error> {
+        match self.writer {
+            Some(ref mut writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+    fn records_written(&self) -> u64 { self.records + self.writer.as_ref().map_or(0, |writer| writer.records_written()) }
+    fn bytes_written(&self) -> u64 { self.bytes + self.writer.as_ref().map_or(0, |writer| writer.bytes_written()) }
+}
+
+// every record goes to both, eg archive a fastq and hand a fasta downstream in one pass. both always
+// get the write and the first error comes back
+pub struct TeeWriter {
+    pub a: Box<DnaWrite>,
+    pub b: Box<DnaWrite>,
+}
+
+impl DnaWrite for TeeWriter {
+    fn write(&mut self, rec: &DnaRecord) -> Result<(), Error> {
+        let a = self.a.write(rec);
+        let b = self.b.write(rec);
+        a.and(b)
+    }
+    fn flush(&mut self) -> Result<(), Error> {
+        let a = self.a.flush();
+        let b = self.b.flush();
+        a.and(b)
+    }
+    // records are counted once, bytes are what went to both
+    fn records_written(&self) -> u64 { self.a.records_written() }
+    fn bytes_written(&self) -> u64 { self.a.bytes_written() + self.b.bytes_written() }
+}
+
+// interleaved readers tell mates apart by the /1 /2 on the read id, so add it if its not already there
+fn with_mate_suffix(rec: &DnaRecord, suffix: &str) -> DnaRecord {
+    let id_len = rec.name.find(char::is_whitespace).unwrap_or(rec.name.len());
+    let (id, rest) = rec.name.split_at(id_len);
+    let name = if id.ends_with("/1") || id.ends_with("/2") { rec.name.clone() } else { format!("{}{}{}", id, suffix, rest) };
+    let mut to_ret = rec.clone();
+    to_ret.name = name;
+    to_ret
+}
+
 pub struct FastaReader {
     pub buf_reader: BufReader<Box<std::io::Read>>,
     pub last_name: Option<String>,
     compression: Compression,
+    pub strict: bool, // blank lines inside a record are an error from try_next (a panic from next) instead of skipped
+    pub allow_lowercase: bool, // only checked when strict, false makes soft masked bases an error
+    pub validate_alphabet: bool, // panic on anything that isnt an iupac base
+    path: Option<String>,
 }
 
 pub struct FastaWriter {
-    pub buf_writer: BufWriter<Box<std::io::Write>>,
+    pub buf_writer: BufWriter<Box<FinishWrite>>,
+    line_width: Option<usize>, // None puts the whole sequence on one line
+    records: u64,
+    bytes: u64,
 }
 
 impl FastaReader {
-    fn new(filename: &str, compression: Compression) -> Self {
-        FastaReader{ buf_reader: get_reader(filename, compression.clone()) , last_name: None, compression: compression}
+    pub fn new(filename: &str, compression: Compression) -> Result<Self, Error> {
+        FastaReader::with_buffer_size(filename, compression, DEFAULT_BUFFER_SIZE)
+    }
+    pub fn with_buffer_size(filename: &str, compression: Compression, buffer_size: usize) -> Result<Self, Error> {
+        let mut reader = FastaReader::from_read_with_buffer_size(open_file(filename)?, compression, buffer_size);
+        reader.path = Some(filename.to_string());
+        Ok(reader)
+    }
+    fn from_read(source: Box<std::io::Read>, compression: Compression) -> Self {
+        FastaReader::from_read_with_buffer_size(source, compression, DEFAULT_BUFFER_SIZE)
+    }
+    fn from_read_with_buffer_size(source: Box<std::io::Read>, compression: Compression, buffer_size: usize) -> Self {
+        FastaReader{ buf_reader: get_reader(source, compression.clone(), buffer_size) , last_name: None, compression,
+            strict: false, allow_lowercase: true, validate_alphabet: false, path: None }
+    }
+    // chunks of at most window bases, each record split on its own so a chunk never spans two records.
+    // only a window of sequence is held at a time. dropping the windows partway through a record leaves
+    // the reader in the middle of it, a window of 0 gives no chunks
+    pub fn windows<'a>(&'a mut self, window: usize) -> FastaWindows<'a> {
+        FastaWindows{ reader: self, window, name: None, start: 0, pending: String::new(), record_ended: false }
+    }
+    fn check_line(&self, name: &str, line: &str) -> Result<(), DnaError> {
+        if !self.strict { return Ok(()); }
+        if line.trim().is_empty() {
+            return Err(DnaError::Parse(format!("blank line inside fasta record {}", name)));
+        }
+        if !self.allow_lowercase && line.bytes().any(|b| b.is_ascii_lowercase()) {
+            return Err(DnaError::Parse(format!("lowercase bases in fasta record {}", name)));
+        }
+        Ok(())
+    }
+}
+
+// name is the record id and start is where seq begins in that record, 0-based
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub struct FastaWindow {
+    pub name: String,
+    pub start: usize,
+    pub seq: String,
+}
+
+pub struct FastaWindows<'a> {
+    reader: &'a mut FastaReader,
+    window: usize,
+    name: Option<String>, // record the chunks are coming from, None between records
+    start: usize,
+    pending: String, // bases read but not handed out yet, never much more than a window
+    record_ended: bool,
+}
+
+impl<'a> Iterator for FastaWindows<'a> {
+    type Item = FastaWindow;
+    fn next(&mut self) -> Option<FastaWindow> {
+        if self.window == 0 { return None; }
+        loop {
+            if self.name.is_none() {
+                let header = match self.reader.last_name.take() {
+                    Some(header) => header,
+                    None => self.reader.read_header()?,
+                };
+                self.name = Some(split_fasta_header(&header).0);
+                self.start = 0;
+                self.record_ended = false;
+            }
+            while !self.record_ended && self.pending.len() < self.window {
+                let mut line = String::new();
+                self.reader.buf_reader.read_line(&mut line).expect("Could not read file");
+                if line.starts_with(">") {
+                    chomp(&mut line);
+                    self.reader.last_name = Some(line);
+                    self.record_ended = true;
+                } else if line.is_empty() {
+                    self.record_ended = true;
+                } else {
+                    if let Some(ref id) = self.name { self.reader.check_line(id, &line).unwrap_or_else(|err| panic!("{}", err)); }
+                    chomp(&mut line);
+                    self.pending.push_str(&line);
+                }
+            }
+            if self.pending.is_empty() {
+                self.name = None; // record ran out exactly on a window boundary, or had no bases
+                continue;
+            }
+            let size = std::cmp::min(self.window, self.pending.len());
+            let seq: String = self.pending.drain(..size).collect();
+            let name = match self.name {
+                Some(ref id) => id.clone(),
+                None => unreachable!(),
+            };
+            if self.reader.validate_alphabet {
+                if let Some(base) = seq.bytes().find(|base| !is_iupac(*base)) {
+                    panic!("record {} has non-nucleotide character {:?} in its sequence", name, base as char);
+                }
+            }
+            let chunk = FastaWindow{ name, start: self.start, seq };
+            self.start += size;
+            if self.record_ended && self.pending.is_empty() { self.name = None; }
+            return Some(chunk);
+        }
+    }
+}
+
+impl FastaWriter {
+	fn new(filename: &str, compression: Compression) -> Self {
+		FastaWriter::with_level(filename, compression, None)
+	}
+    pub fn with_level(filename: &str, compression: Compression, level: Option<u32>) -> Self {
+        FastaWriter::from_write_with_level(create_file(filename), compression, level)
+    }
+	fn from_write(sink: Box<std::io::Write>, compression: Compression) -> Self {
+		FastaWriter::from_write_with_level(sink, compression, None)
+	}
+    fn from_write_with_level(sink: Box<std::io::Write>, compression: Compression, level: Option<u32>) -> Self {
+		FastaWriter{ buf_writer: get_writer(sink, compression, level), line_width: Some(FASTA_LINE_WIDTH), records: 0, bytes: 0 }
+    }
+    pub fn with_line_width(filename: &str, compression: Compression, line_width: Option<usize>) -> Self {
+        let mut writer = FastaWriter::new(filename, compression);
+        writer.set_line_width(line_width);
+        writer
+    }
+    pub fn set_line_width(&mut self, line_width: Option<usize>) {
+        assert!(line_width != Some(0), "fasta line width must be at least 1");
+        self.line_width = line_width;
+    }
+}
+
+
+// drops the line ending read_line leaves on, the last line of a file might not have one and files
+// from windows have \r\n
+fn chomp(line: &mut String) {
+    let len = line.trim_end_matches(['\r', '\n']).len();
+    line.truncate(len);
+}
+
+// ">id the rest" into the id and the rest, None when there is no rest
+fn split_fasta_header(line: &str) -> (String, Option<String>) {
+    let line = line.trim_start_matches('>').trim();
+    match line.find(char::is_whitespace) {
+        Some(i) => (line[..i].to_string(), Some(line[i..].trim_start().to_string())),
+        None => (line.to_string(), None),
+    }
+}
+
+fn fastq_header(rec: &DnaRecord) -> String {
+    match rec.description {
+        Some(ref description) => format!("@{} {}", rec.name, description),
+        None => format!("@{}", rec.name),
+    }
+}
+
+const FASTA_LINE_WIDTH: usize = 60;
+
+fn fasta_string(rec: &DnaRecord, line_width: Option<usize>) -> String {
+    let mut to_ret = fasta_header(rec);
+    to_ret.push('\n');
+    match line_width {
+        None => {
+            to_ret.push_str(&rec.seq);
+            to_ret.push('\n');
+        },
+        Some(width) => {
+            // chunks of bytes, the seq is ascii so this never splits a char
+            for line in rec.seq.as_bytes().chunks(width) {
+                to_ret.push_str(&String::from_utf8_lossy(line));
+                to_ret.push('\n');
+            }
+        },
+    }
+    to_ret
+}
+
+fn fasta_header(rec: &DnaRecord) -> String {
+    match rec.description {
+        Some(ref description) => format!(">{} {}", rec.name, description),
+        None => format!(">{}", rec.name),
     }
 }
 
-impl FastaWriter {
-	fn new(filename: &str, compression: Compression) -> Self {
-		FastaWriter{ buf_writer: get_writer(filename, compression) }
-	}
+// reuses one buffer for every line, for the count_records fast paths
+fn count_lines<F: Fn(&[u8]) -> bool>(buf_reader: &mut BufReader<Box<std::io::Read>>, keep: F) -> u64 {
+    let mut line = Vec::new();
+    let mut count = 0;
+    loop {
+        line.clear();
+        match buf_reader.read_until(b'\n', &mut line).expect("Could not read file") {
+            0 => return count,
+            _ => if keep(&line) { count += 1; },
+        }
+    }
 }
 
-
-impl DnaRead for FastaReader {
-	fn next(&mut self) -> Option<DnaRecord> {
+impl FastaReader {
+    // the first header with nothing held over from the last record. blank lines before it, or a file thats
+    // nothing but, arent records
+    fn read_header(&mut self) -> Option<String> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match self.buf_reader.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) => if !line.trim().is_empty() { break; },
+                Err(err) => panic!("{}",err),
+            }
+        }
+        if !line.starts_with(">") { panic!("not fasta format?"); }
+        chomp(&mut line);
+        Some(line)
+    }
+    // the strict checks come back as errors, the bad record is still read to the end so the one after it is next
+    fn read_record(&mut self) -> Result<Option<DnaRecord>, DnaError> {
         let mut name = String::new();
         let mut seq = String::new();
         let mut next_name = String::new();
+        let mut error = None;
 
         match self.last_name {
             Some(ref my_name) => {
-                println!("got here");
                 name.push_str(my_name);
                 'line_iter: loop {
                     let mut line = String::new();
@@ -248,15 +2531,16 @@ impl DnaRead for FastaReader {
                         Ok(_) => {
                             if line.starts_with(">") {
                                 next_name.push_str(&line);
-                                next_name.pop();
+                                chomp(&mut next_name);
                                 break 'line_iter;
                             }
                             else if line.is_empty() {
                                 break 'line_iter; 
                             }
                             else {
+                                if error.is_none() { error = self.check_line(&name, &line).err(); }
                                 seq.push_str(&line);
-                                seq.pop();
+                                chomp(&mut seq);
                             }
                         },
                         Err(err) => {
@@ -266,38 +2550,24 @@ impl DnaRead for FastaReader {
                 }
             },
             None => {
-                println!("first");
-                let mut line = String::new();
-                match self.buf_reader.read_line(&mut line) {
-                    
-                    Ok(_) => {
-                        println!("{}",line);
-                        if line.starts_with(">") {
-                            name.push_str(&line);
-                            name.pop();
-                        } else if line.is_empty() {
-                            println!("empty");
-                            return None;
-                        } else {
-                            panic!("not fasta format?");
-                        }
-                    },
-                    Err(err) => panic!("{}",err),
+                match self.read_header() {
+                    Some(header) => name.push_str(&header),
+                    None => return Ok(None),
                 }
                 'line_iter2: loop {
                     let mut line = String::new();
                     match self.buf_reader.read_line(&mut line) {
-                        Ok(x) => {
-                            println!("here {} {}", line,x);
+                        Ok(_) => {
                             if line.starts_with(">") {
                                 next_name.push_str(&line);
-                                next_name.pop();
+                                chomp(&mut next_name);
                                 break 'line_iter2;
                             }
                             else if line.is_empty() { break 'line_iter2; }
                             else {
+                                if error.is_none() { error = self.check_line(&name, &line).err(); }
                                 seq.push_str(&line);
-                                seq.pop();
+                                chomp(&mut seq);
                             }
                         },
                         Err(err) => {
@@ -312,86 +2582,964 @@ impl DnaRead for FastaReader {
         } else {
             self.last_name = None;
         }
-        Some(DnaRecord{ name: name, seq: seq, qual: None })
+        if let Some(err) = error { return Err(err); }
+        let (id, description) = split_fasta_header(&name);
+        Ok(Some(DnaRecord{ name: id, description, seq, qual: None, ..Default::default() }))
+	}
+}
+
+impl DnaRead for FastaReader {
+    // panics on the strict mode errors like the fastq reader, try_next hands them back instead
+	fn next(&mut self) -> Option<DnaRecord> {
+        match self.try_next() {
+            Ok(rec) => rec,
+            Err(err) => panic!("{}", err),
+        }
 	}
+    fn try_next(&mut self) -> Result<Option<DnaRecord>, DnaError> {
+        let rec = match self.read_record()? {
+            Some(rec) => rec,
+            None => return Ok(None),
+        };
+        if self.validate_alphabet { check_alphabet(&rec)?; }
+        Ok(Some(rec))
+    }
     fn header(&self) -> Option<bam::Header> { None }
+    fn count_records(&mut self) -> u64 {
+        let pending = match self.last_name.take() { Some(_) => 1, None => 0 };
+        pending + count_lines(&mut self.buf_reader, |line| line.starts_with(b">"))
+    }
     fn my_type(&self) -> DnaFormat { Fasta }
     fn extension(&self) -> String {
         let mut to_ret = ".fasta".to_string();
-        if self.compression == Gzipped { to_ret.push_str(".gz"); }
+        to_ret.push_str(compression_extension(&self.compression));
         to_ret
     }
+    fn reset(&mut self) -> Result<(), DnaError> {
+        self.buf_reader = reopen_reader(&self.path, &self.compression, self.buf_reader.capacity())?;
+        self.last_name = None;
+        Ok(())
+    }
 }
 
 impl DnaWrite for FastaWriter {
 	fn write(&mut self, rec: &DnaRecord) -> Result<(), Error> {
-        let to_write = format!("{}\n{}\n",rec.name, rec.seq);
-        self.buf_writer.write_all(&to_write.as_bytes())
+        let to_write = fasta_string(rec, self.line_width);
+        self.buf_writer.write_all(to_write.as_bytes())?;
+        self.records += 1;
+        self.bytes += to_write.len() as u64;
+        Ok(())
 	}
+    fn flush(&mut self) -> Result<(), Error> {
+        self.buf_writer.flush()?;
+        self.buf_writer.get_mut().finish()
+    }
+    fn records_written(&self) -> u64 { self.records }
+    fn bytes_written(&self) -> u64 { self.bytes }
+}
+
+// one line of a .fai: where the bases of a record start and how its lines are laid out
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub struct FaiEntry {
+    pub name: String,
+    pub length: u64,
+    pub offset: u64, // byte offset of the first base
+    pub line_bases: u64,
+    pub line_bytes: u64, // line_bases plus the newline, 2 more for \r\n
+}
+
+// writes fasta_path.fai like samtools faidx does. every line in a record but the last has to be the
+// same length, anything else cant be described by a .fai
+pub fn build_fai(fasta_path: &str) -> Result<(), DnaError> {
+    if let Some((_, compression)) = extension_format(fasta_path) {
+        if compression != Uncompressed {
+            return Err(DnaError::Unsupported(format!("can only index uncompressed fasta, not {}", fasta_path)));
+        }
+    }
+    let entries = scan_fai(fasta_path)?;
+    let mut fai = BufWriter::new(File::create(format!("{}.fai", fasta_path))?);
+    for entry in entries.iter() {
+        writeln!(fai, "{}\t{}\t{}\t{}\t{}", entry.name, entry.length, entry.offset, entry.line_bases, entry.line_bytes)?;
+    }
+    fai.flush()?;
+    Ok(())
+}
+
+// reads just the bases asked for, using a .fai like the one build_fai makes
+pub struct IndexedFastaReader {
+    file: File,
+    index: HashMap<String, FaiEntry>,
+}
+
+impl IndexedFastaReader {
+    pub fn from_path(fasta_path: &str, fai_path: &str) -> Result<Self, DnaError> {
+        let mut index = HashMap::new();
+        for line in BufReader::new(File::open(fai_path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() { continue; }
+            let fields: Vec<&str> = line.split('\t').collect();
+            let bad_line = || DnaError::Parse(format!("bad line in {}: {}", fai_path, line));
+            if fields.len() < 5 { return Err(bad_line()); }
+            let mut numbers = Vec::new();
+            for field in fields[1..5].iter() {
+                numbers.push(field.parse::<u64>().map_err(|_| bad_line())?);
+            }
+            index.insert(fields[0].to_string(), FaiEntry{ name: fields[0].to_string(), length: numbers[0], offset: numbers[1],
+                line_bases: numbers[2], line_bytes: numbers[3] });
+        }
+        Ok(IndexedFastaReader{ file: File::open(fasta_path)?, index })
+    }
+    // 1-based and inclusive like samtools faidx, eg fetch("chr1", 5, 10) for chr1:5-10. an end past
+    // the end of the sequence stops at the last base, the record is named like chr1:5-10
+    pub fn fetch(&mut self, name: &str, start: usize, end: usize) -> Result<DnaRecord, DnaError> {
+        let entry = match self.index.get(name) {
+            Some(entry) => entry.clone(),
+            None => return Err(DnaError::Parse(format!("no sequence named {} in the index", name))),
+        };
+        let end = std::cmp::min(end as u64, entry.length);
+        if start == 0 || start as u64 > end {
+            return Err(DnaError::Parse(format!("{}:{}-{} is not a region of {} (length {})", name, start, end, name, entry.length)));
+        }
+        // byte where a 0-based base sits, whole lines before it plus how far into its line
+        let base_offset = |i: u64| entry.offset + (i / entry.line_bases) * entry.line_bytes + i % entry.line_bases;
+        let first = base_offset(start as u64 - 1);
+        let last = base_offset(end - 1);
+        let mut bytes = vec![0u8; (last - first + 1) as usize];
+        self.file.seek(std::io::SeekFrom::Start(first))?;
+        self.file.read_exact(&mut bytes)?;
+        let seq: String = bytes.iter().filter(|&&b| b != b'\n' && b != b'\r').map(|&b| b as char).collect();
+        Ok(DnaRecord{ name: format!("{}:{}-{}", name, start, end), seq, ..Default::default() })
+    }
+    pub fn names(&self) -> Vec<String> { self.index.keys().cloned().collect() }
+    pub fn length(&self, name: &str) -> Option<u64> { self.index.get(name).map(|entry| entry.length) }
+}
+
+// one record per bed interval, pulled out through fasta.fai. bed is 0-based half open and the names
+// keep its coordinates like bedtools getfasta, eg chr1:8-14. - strand intervals come back reverse complemented
+// and start == end is an empty record. not being able to open either file is the outer error, a bad line
+// or an interval that isnt in the fasta is an error for that record and the rest still come out
+pub fn extract_bed(fasta: &str, bed: &str) -> Result<impl Iterator<Item=Result<DnaRecord, DnaError>>, DnaError> {
+    let fai = format!("{}.fai", fasta);
+    let mut reader = IndexedFastaReader::from_path(fasta, &fai)?;
+    let lines = BufReader::new(File::open(bed)?).lines();
+    Ok(lines.filter(|line| match *line {
+            Ok(ref line) => !(line.trim().is_empty() || line.starts_with('#') || line.starts_with("track") || line.starts_with("browser")),
+            Err(_) => true,
+        })
+        .map(move |line| {
+            let line = line?;
+            let fields: Vec<&str> = line.split('\t').collect();
+            let bad_line = |what: &str| DnaError::Parse(format!("bed line {} {}", line, what));
+            if fields.len() < 3 { return Err(bad_line("needs chrom, start and end")); }
+            let start: usize = fields[1].parse().map_err(|_| bad_line("has a start that is not a number"))?;
+            let end: usize = fields[2].parse().map_err(|_| bad_line("has an end that is not a number"))?;
+            if start > end { return Err(bad_line("ends before it starts")); }
+            let rec = if start == end {
+                if reader.length(fields[0]).is_none() { return Err(bad_line("is on a sequence that is not in the index")); }
+                DnaRecord::default()
+            } else {
+                reader.fetch(fields[0], start + 1, end)?
+            };
+            let mut rec = if fields.len() > 5 && fields[5] == "-" { rec.reverse_complement() } else { rec };
+            rec.name = format!("{}:{}-{}", fields[0], start, end);
+            Ok(rec)
+        }))
+}
+
+fn scan_fai(fasta_path: &str) -> Result<Vec<FaiEntry>, DnaError> {
+    let mut reader = BufReader::new(File::open(fasta_path)?);
+    let mut entries = Vec::new();
+    let mut current: Option<FaiEntry> = None;
+    let mut offset = 0u64;
+    let mut short_line = false; // seen the last line of the record already, only blank lines can follow
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let bytes = reader.read_until(b'\n', &mut line)? as u64;
+        if bytes == 0 { break; }
+        offset += bytes;
+        if line[0] == b'>' {
+            if let Some(entry) = current.take() { entries.push(entry); }
+            let header = String::from_utf8_lossy(&line[1..]).to_string();
+            let name = header.split_whitespace().next().unwrap_or("").to_string();
+            current = Some(FaiEntry{ name, length: 0, offset, line_bases: 0, line_bytes: 0 });
+            short_line = false;
+            continue;
+        }
+        let entry = match current {
+            Some(ref mut entry) => entry,
+            None => return Err(DnaError::Parse(format!("{} does not start with a > header", fasta_path))),
+        };
+        let bases = line.iter().filter(|&&b| b != b'\n' && b != b'\r').count() as u64;
+        if bases == 0 { short_line = true; continue; }
+        let uneven = || DnaError::Parse(format!("record {} has lines of different lengths, a .fai cant describe it", entry.name));
+        if short_line { return Err(uneven()); }
+        if entry.line_bases == 0 {
+            entry.line_bases = bases;
+            entry.line_bytes = bytes;
+        } else if bases > entry.line_bases || (bases == entry.line_bases && line.ends_with(b"\n") && bytes != entry.line_bytes) {
+            return Err(uneven());
+        }
+        if bases < entry.line_bases { short_line = true; }
+        entry.length += bases;
+    }
+    if let Some(entry) = current.take() { entries.push(entry); }
+    Ok(entries)
+}
+
+// names are made up as seq_0, seq_1, .. in file order
+pub struct RawReader {
+    pub buf_reader: BufReader<Box<std::io::Read>>,
+    compression: Compression,
+    count: usize,
+    path: Option<String>,
+}
+
+pub struct RawWriter {
+    pub buf_writer: BufWriter<Box<FinishWrite>>,
+    records: u64,
+    bytes: u64,
+}
+
+impl RawReader {
+    pub fn new(filename: &str, compression: Compression) -> Result<Self, Error> {
+        let mut reader = RawReader::from_read(open_file(filename)?, compression);
+        reader.path = Some(filename.to_string());
+        Ok(reader)
+    }
+    fn from_read(source: Box<std::io::Read>, compression: Compression) -> Self {
+        RawReader{ buf_reader: get_reader(source, compression.clone(), DEFAULT_BUFFER_SIZE), compression, count: 0, path: None }
+    }
+}
+
+impl DnaRead for RawReader {
+    fn next(&mut self) -> Option<DnaRecord> {
+        loop {
+            let mut line = String::new();
+            if self.buf_reader.read_line(&mut line).expect("Could not read file") == 0 { return None; }
+            let seq = line.trim_end();
+            if seq.is_empty() { continue; }
+            let rec = DnaRecord{ name: format!("seq_{}", self.count), seq: seq.to_string(), qual: None, ..Default::default() };
+            self.count += 1;
+            return Some(rec);
+        }
+    }
+    fn count_records(&mut self) -> u64 {
+        count_lines(&mut self.buf_reader, |line| !line.iter().all(|b| b.is_ascii_whitespace()))
+    }
+    fn my_type(&self) -> DnaFormat { Raw }
+    fn header(&self) -> Option<bam::Header> { None }
+    fn extension(&self) -> String {
+        let mut to_ret = ".txt".to_string();
+        to_ret.push_str(compression_extension(&self.compression));
+        to_ret
+    }
+    fn reset(&mut self) -> Result<(), DnaError> {
+        self.buf_reader = reopen_reader(&self.path, &self.compression, self.buf_reader.capacity())?;
+        self.count = 0;
+        Ok(())
+    }
+}
+
+impl RawWriter {
+    fn new(filename: &str, compression: Compression) -> Self {
+        RawWriter::with_level(filename, compression, None)
+    }
+    pub fn with_level(filename: &str, compression: Compression, level: Option<u32>) -> Self {
+        RawWriter::from_write(create_file(filename), compression, level)
+    }
+    fn from_write(sink: Box<std::io::Write>, compression: Compression, level: Option<u32>) -> Self {
+        RawWriter{ buf_writer: get_writer(sink, compression, level), records: 0, bytes: 0 }
+    }
+}
+
+impl DnaWrite for RawWriter {
+    fn write(&mut self, rec: &DnaRecord) -> Result<(), Error> {
+        self.buf_writer.write_all(rec.seq.as_bytes())?;
+        self.buf_writer.write_all(b"\n")?;
+        self.records += 1;
+        self.bytes += rec.seq.len() as u64 + 1;
+        Ok(())
+    }
+    fn flush(&mut self) -> Result<(), Error> {
+        self.buf_writer.flush()?;
+        self.buf_writer.get_mut().finish()
+    }
+    fn records_written(&self) -> u64 { self.records }
+    fn bytes_written(&self) -> u64 { self.bytes }
+}
+
+// name<TAB>seq<TAB>qual, qual column left off when the record has none. names with tabs in them wont round trip
+pub struct TsvReader {
+    pub buf_reader: BufReader<Box<std::io::Read>>,
+    compression: Compression,
+    path: Option<String>,
+}
+
+pub struct TsvWriter {
+    pub buf_writer: BufWriter<Box<FinishWrite>>,
+    records: u64,
+    bytes: u64,
+}
+
+impl TsvReader {
+    pub fn new(filename: &str, compression: Compression) -> Result<Self, Error> {
+        let mut reader = TsvReader::from_read(open_file(filename)?, compression);
+        reader.path = Some(filename.to_string());
+        Ok(reader)
+    }
+    fn from_read(source: Box<std::io::Read>, compression: Compression) -> Self {
+        TsvReader{ buf_reader: get_reader(source, compression.clone(), DEFAULT_BUFFER_SIZE), compression, path: None }
+    }
+}
+
+impl DnaRead for TsvReader {
+    fn next(&mut self) -> Option<DnaRecord> {
+        loop {
+            let mut line = String::new();
+            if self.buf_reader.read_line(&mut line).expect("Could not read file") == 0 { return None; }
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() { continue; }
+            let fields: Vec<&str> = line.split('\t').collect();
+            let qual = match fields.len() {
+                2 => None,
+                3 => Some(fields[2].to_string()),
+                n => panic!("tsv line has {} columns, expected name, seq and maybe qual: {}", n, line),
+            };
+            return Some(DnaRecord{ name: fields[0].to_string(), seq: fields[1].to_string(), qual, ..Default::default() });
+        }
+    }
+    fn count_records(&mut self) -> u64 {
+        count_lines(&mut self.buf_reader, |line| !line.iter().all(|b| b.is_ascii_whitespace()))
+    }
+    fn my_type(&self) -> DnaFormat { Tsv }
+    fn header(&self) -> Option<bam::Header> { None }
+    fn extension(&self) -> String {
+        let mut to_ret = ".tsv".to_string();
+        to_ret.push_str(compression_extension(&self.compression));
+        to_ret
+    }
+    fn reset(&mut self) -> Result<(), DnaError> {
+        self.buf_reader = reopen_reader(&self.path, &self.compression, self.buf_reader.capacity())?;
+        Ok(())
+    }
+}
+
+impl TsvWriter {
+    fn new(filename: &str, compression: Compression) -> Self {
+        TsvWriter::with_level(filename, compression, None)
+    }
+    pub fn with_level(filename: &str, compression: Compression, level: Option<u32>) -> Self {
+        TsvWriter::from_write(create_file(filename), compression, level)
+    }
+    fn from_write(sink: Box<std::io::Write>, compression: Compression, level: Option<u32>) -> Self {
+        TsvWriter{ buf_writer: get_writer(sink, compression, level), records: 0, bytes: 0 }
+    }
+}
+
+impl DnaWrite for TsvWriter {
+    fn write(&mut self, rec: &DnaRecord) -> Result<(), Error> {
+        self.buf_writer.write_all(rec.name.as_bytes())?;
+        self.buf_writer.write_all(b"\t")?;
+        self.buf_writer.write_all(rec.seq.as_bytes())?;
+        if let Some(ref qual) = rec.qual {
+            self.buf_writer.write_all(b"\t")?;
+            self.buf_writer.write_all(qual.as_bytes())?;
+        }
+        self.buf_writer.write_all(b"\n")?;
+        self.records += 1;
+        self.bytes += (rec.name.len() + rec.seq.len() + 2 + rec.qual.as_ref().map_or(0, |qual| qual.len() + 1)) as u64;
+        Ok(())
+    }
+    fn flush(&mut self) -> Result<(), Error> {
+        self.buf_writer.flush()?;
+        self.buf_writer.get_mut().finish()
+    }
+    fn records_written(&self) -> u64 { self.records }
+    fn bytes_written(&self) -> u64 { self.bytes }
+}
+
+// ucsc 2bit, https://genome.ucsc.edu/FAQ/FAQformat.html#format7. the index up front says where each
+// sequence starts so we seek to them one at a time instead of reading the whole genome in
+pub struct TwoBitReader {
+    file: BufReader<File>,
+    index: VecDeque<(String, u64)>,
+    big_endian: bool,
+    path: String,
+}
+
+const TWOBIT_SIGNATURE: u32 = 0x1A412743;
+
+impl TwoBitReader {
+    pub fn new(filename: &str) -> Result<Self, Error> {
+        let file = BufReader::new(File::open(filename)?);
+        let mut reader = TwoBitReader{ file, index: VecDeque::new(), big_endian: false, path: filename.to_string() };
+        let signature = reader.read_u32()?;
+        if signature != TWOBIT_SIGNATURE {
+            if signature.swap_bytes() != TWOBIT_SIGNATURE {
+                return Err(Error::new(ErrorKind::InvalidData, format!("{} is not a 2bit file", filename)));
+            }
+            reader.big_endian = true;
+        }
+        let _version = reader.read_u32()?;
+        let seq_count = reader.read_u32()?;
+        let _reserved = reader.read_u32()?;
+        for _ in 0..seq_count {
+            let mut name_size = [0u8; 1];
+            reader.file.read_exact(&mut name_size)?;
+            let mut name = vec![0u8; name_size[0] as usize];
+            reader.file.read_exact(&mut name)?;
+            let offset = reader.read_u32()?;
+            reader.index.push_back((String::from_utf8_lossy(&name).to_string(), offset as u64));
+        }
+        Ok(reader)
+    }
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        let mut bytes = [0u8; 4];
+        self.file.read_exact(&mut bytes)?;
+        let value = u32::from_le_bytes(bytes);
+        Ok(if self.big_endian { value.swap_bytes() } else { value })
+    }
+    // (start, size) pairs, stored as all the starts then all the sizes
+    fn read_blocks(&mut self) -> Result<Vec<(usize, usize)>, Error> {
+        let count = self.read_u32()? as usize;
+        let mut starts = Vec::with_capacity(count);
+        for _ in 0..count { starts.push(self.read_u32()? as usize); }
+        let mut blocks = Vec::with_capacity(count);
+        for start in starts { blocks.push((start, self.read_u32()? as usize)); }
+        Ok(blocks)
+    }
+    fn read_sequence(&mut self, offset: u64) -> Result<String, Error> {
+        self.file.seek(std::io::SeekFrom::Start(offset))?;
+        let dna_size = self.read_u32()? as usize;
+        let n_blocks = self.read_blocks()?;
+        let mask_blocks = self.read_blocks()?;
+        let _reserved = self.read_u32()?;
+        let mut packed = vec![0u8; dna_size.div_ceil(4)];
+        self.file.read_exact(&mut packed)?;
+        let mut seq: Vec<u8> = Vec::with_capacity(dna_size);
+        for i in 0..dna_size {
+            let bits = (packed[i / 4] >> (6 - 2 * (i % 4))) & 0b11;
+            seq.push(b"TCAG"[bits as usize]);
+        }
+        for (start, size) in n_blocks {
+            for base in seq.iter_mut().skip(start).take(size) { *base = b'N'; }
+        }
+        for (start, size) in mask_blocks {
+            for base in seq.iter_mut().skip(start).take(size) { *base = base.to_ascii_lowercase(); }
+        }
+        Ok(String::from_utf8(seq).expect("2bit bases are always ascii"))
+    }
+}
+
+impl DnaRead for TwoBitReader {
+    fn next(&mut self) -> Option<DnaRecord> {
+        let (name, offset) = self.index.pop_front()?;
+        let seq = match self.read_sequence(offset) {
+            Ok(seq) => seq,
+            Err(err) => panic!("could not read 2bit sequence {}: {}", name, err),
+        };
+        Some(DnaRecord{ name, seq, qual: None, ..Default::default() })
+    }
+    fn count_records(&mut self) -> u64 {
+        let count = self.index.len() as u64;
+        self.index.clear();
+        count
+    }
+    fn my_type(&self) -> DnaFormat { TwoBit }
+    fn header(&self) -> Option<bam::Header> { None }
+    fn extension(&self) -> String { ".2bit".to_string() }
+    fn reset(&mut self) -> Result<(), DnaError> {
+        *self = TwoBitReader::new(&self.path)?;
+        Ok(())
+    }
 }
 
 pub struct BamReader {
     pub reader: bam::Reader,
+    indexed: Option<bam::IndexedReader>, // opened on the first fetch, next() reads from here after that
+    path: Option<String>,
+    skip_flags: u16, // records with any of these flags set are passed over
+    tags: Vec<String>,
+    original_orientation: bool,
 }
 
+const BAM_FUNMAP: u16 = 0x4;
+const BAM_FREVERSE: u16 = 0x10;
+const BAM_FSECONDARY: u16 = 0x100;
+const BAM_FSUPPLEMENTARY: u16 = 0x800;
+const BAM_FREAD1: u16 = 0x40;
+
 pub struct BamWriter {
-	pub writer: bam::Writer,
+	writer: HtsWriter,
+    records: u64,
+}
+
+// rust_htslib 0.24 always opens bam with mode "wb", keeps the htsFile to itself and only closes it in drop,
+// throwing away the return code. so bam/sam/cram go through hts_open ourselves ("wb1".."wb9" for a level,
+// "w" for sam, "wc" for cram) doing the header/record writes its Writer would, and close can say if
+// the end of the file didnt make it out
+struct HtsWriter {
+    f: *mut htslib::htsFile,
+    header: bam::HeaderView,
+}
+
+impl HtsWriter {
+    fn open(filename: &str, header: &bam::Header, mode: &str, reference: Option<&str>) -> Result<Self, Error> {
+        let path = std::ffi::CString::new(filename)?;
+        let mode = std::ffi::CString::new(mode)?;
+        let f = unsafe { htslib::hts_open(path.as_ptr(), mode.as_ptr()) };
+        if f.is_null() { return Err(Error::other(format!("could not open {} for writing", filename))); }
+        if let Some(reference) = reference {
+            let reference = std::ffi::CString::new(reference)?;
+            if unsafe { htslib::hts_set_fai_filename(f, reference.as_ptr()) } < 0 {
+                unsafe { htslib::hts_close(f); }
+                return Err(Error::other(format!("could not set the reference for {}, is there a .fai for it?", filename)));
+            }
+        }
+        // same as rust_htslib, sam_hdr_parse leaves the text out so it has to be copied in by hand
+        // (malloced, bam_hdr_destroy frees it) or the non @SQ lines dont make it into the file
+        let mut text = header.to_bytes();
+        if !text.is_empty() && text[text.len() - 1] != b'\n' { text.push(b'\n'); }
+        let inner = unsafe {
+            let buf = libc::calloc(text.len() + 1, 1) as *mut libc::c_char;
+            std::ptr::copy_nonoverlapping(text.as_ptr() as *const libc::c_char, buf, text.len());
+            let inner = htslib::sam_hdr_parse((text.len() + 1) as i32, buf);
+            if inner.is_null() {
+                libc::free(buf as *mut libc::c_void);
+                htslib::hts_close(f);
+                return Err(Error::new(ErrorKind::InvalidInput, format!("htslib could not parse the header for {}", filename)));
+            }
+            (*inner).text = buf;
+            (*inner).l_text = text.len() as u32;
+            inner
+        };
+        let writer = HtsWriter{ f, header: bam::HeaderView::new(inner) };
+        if unsafe { htslib::sam_hdr_write(writer.f, writer.header.inner_ptr()) } < 0 {
+            return Err(Error::other(format!("could not write the header to {}", filename)));
+        }
+        Ok(writer)
+    }
+    fn write(&mut self, rec: &bam::Record) -> Result<(), Error> {
+        if self.f.is_null() { return Err(Error::other("file was already closed")); }
+        match unsafe { htslib::sam_write1(self.f, self.header.inner_ptr(), rec.inner) } {
+            -1 => Err(Error::other("htslib failed to write the record")),
+            _ => Ok(()),
+        }
+    }
+    // closing is what writes the last block/container and the EOF marker, so a failure there means a truncated file
+    fn close(&mut self) -> Result<(), Error> {
+        if self.f.is_null() { return Ok(()); }
+        let ret = unsafe { htslib::hts_close(self.f) };
+        self.f = std::ptr::null_mut();
+        if ret < 0 { return Err(Error::other("htslib failed to finish the file, it is probably truncated")); }
+        Ok(())
+    }
+}
+
+// only when close wasnt called, nowhere to send an error from here
+impl Drop for HtsWriter {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
 }
 
 impl BamReader {
-    fn new(filename: &str) -> Self {
-        let bam = bam::Reader::from_path(filename).expect("could not open file for bam reading");
-        BamReader { reader: bam }
+    pub fn new(filename: &str) -> Result<Self, Error> {
+        File::open(filename)?; // so a missing file comes back as NotFound rather than an htslib error
+        match bam::Reader::from_path(filename) {
+            Ok(bam) => Ok(BamReader { reader: bam, indexed: None, path: Some(filename.to_string()), skip_flags: 0, tags: Vec::new(),
+                original_orientation: false }),
+            Err(err) => Err(Error::new(ErrorKind::InvalidData, format!("could not open file for bam reading: {}", err))),
+        }
+    }
+    fn from_stdin() -> Self {
+        let bam = bam::Reader::from_stdin().expect("could not open stdin for bam reading");
+        BamReader { reader: bam, indexed: None, path: None, skip_flags: 0, tags: Vec::new(), original_orientation: false }
+    }
+    // eg BamReader::new(path)?.skip_secondary(true).skip_supplementary(true) for one record per read
+    pub fn skip_secondary(self, skip: bool) -> Self { self.skip_flag(BAM_FSECONDARY, skip) }
+    pub fn skip_supplementary(self, skip: bool) -> Self { self.skip_flag(BAM_FSUPPLEMENTARY, skip) }
+    pub fn skip_unmapped(self, skip: bool) -> Self { self.skip_flag(BAM_FUNMAP, skip) }
+    // copies these aux tags onto each record, values as text. rust_htslib cant list the tags a record
+    // has so they have to be asked for by name, tags a record doesnt have are left out
+    pub fn with_tags(mut self, tags: &[&str]) -> Self {
+        self.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        self
+    }
+    // reverse strand reads are stored reverse complemented, this flips them back to how they came off
+    // the sequencer, eg for going back to fastq. the 0x10 flag is left on
+    pub fn original_orientation(mut self, on: bool) -> Self {
+        self.original_orientation = on;
+        self
+    }
+    // mates together, read1 first, for a bam sorted by read name (@HD SO:queryname). secondary and
+    // supplementary alignments are skipped so each read shows up once
+    pub fn pairs<'a>(&'a mut self) -> Result<BamPairs<'a>, DnaError> {
+        let text = String::from_utf8_lossy(self.reader.header().as_bytes()).to_string();
+        let name_sorted = text.lines().filter(|line| line.starts_with("@HD")).any(|line| line.split('\t').any(|field| field == "SO:queryname"));
+        if !name_sorted { return Err(DnaError::Unsupported("pairs needs a bam sorted by read name, eg samtools sort -n".to_string())); }
+        Ok(BamPairs{ reader: self })
+    }
+    fn read_next(&mut self, skip_flags: u16) -> Option<DnaRecord> {
+        match self.indexed {
+            Some(ref mut indexed) => read_bam_record(indexed, skip_flags, &self.tags),
+            None => read_bam_record(&mut self.reader, skip_flags, &self.tags),
+        }.map(|rec| if self.original_orientation { unreverse(rec) } else { rec })
+    }
+    fn skip_flag(mut self, flag: u16, skip: bool) -> Self {
+        if skip { self.skip_flags |= flag; } else { self.skip_flags &= !flag; }
+        self
+    }
+    // samtools style region, chr1 or chr1:1000 or chr1:1000-2000, 1-based and inclusive. needs a .bai next to the bam
+    pub fn fetch(&mut self, region: &str) -> Result<(), DnaError> {
+        let (contig, beg, end) = parse_region(region)?;
+        if self.indexed.is_none() {
+            let path = match self.path {
+                Some(ref path) => path.clone(),
+                None => return Err(DnaError::Unsupported("cant fetch from a bam on stdin".to_string())),
+            };
+            match bam::IndexedReader::from_path(&path) {
+                Ok(indexed) => self.indexed = Some(indexed),
+                Err(err) => return Err(DnaError::Io(Error::new(ErrorKind::NotFound, format!("could not load index for {}: {}", path, err)))),
+            }
+        }
+        let indexed = self.indexed.as_mut().expect("index was just loaded");
+        let tid = match indexed.header().tid(contig.as_bytes()) {
+            Some(tid) => tid,
+            None => return Err(DnaError::Parse(format!("contig {} is not in the bam header", contig))),
+        };
+        let end = match end {
+            Some(end) => end,
+            None => indexed.header().target_len(tid).unwrap_or(u32::MAX),
+        };
+        match indexed.fetch(tid, beg, end) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(DnaError::Io(Error::other(format!("fetch of {} failed: {}", region, err)))),
+        }
     }
 }
 
 impl BamWriter {
-	fn new(filename: &str, template: bam::Reader) -> Self {
-		let header = bam::Header::from_template(template.header());
-		let mut bam = bam::Writer::from_path(filename, &header).expect("could not open bam for writing");
-		BamWriter{ writer: bam }
+	fn new(filename: &str, template: &DnaReader, level: Option<u32>) -> Self {
+        // fastq/fasta have no header, unmapped records dont need any @SQ lines
+		let header = template.header().unwrap_or_else(bam::Header::new);
+		BamWriter::with_level(filename, &header, level)
 	}
+    pub fn from_header(filename: &str, header: &bam::Header) -> Self {
+        BamWriter::with_level(filename, header, None)
+    }
+    // 1 is fastest, 9 is smallest, None is htslib's default of 6
+    pub fn with_level(filename: &str, header: &bam::Header, level: Option<u32>) -> Self {
+        BamWriter::try_with_level(filename, header, level).expect("could not open bam for writing")
+    }
+    pub fn try_with_level(filename: &str, header: &bam::Header, level: Option<u32>) -> Result<Self, Error> {
+        let mode = match level {
+            None => "wb".to_string(),
+            Some(level) => format!("wb{}", level.min(9)),
+        };
+		Ok(BamWriter{ writer: HtsWriter::open(filename, header, &mode, None)?, records: 0 })
+    }
+}
+
+// contig plus 0-based half open start/end, end is None when the region runs to the end of the contig
+fn parse_region(region: &str) -> Result<(String, u32, Option<u32>), DnaError> {
+    let bad_region = || DnaError::Parse(format!("could not parse region {}", region));
+    let colon = match region.rfind(':') {
+        Some(colon) => colon,
+        None => return Ok((region.to_string(), 0, None)),
+    };
+    let (contig, range) = (&region[..colon], region[colon+1..].replace(",", ""));
+    let mut bounds = range.splitn(2, '-');
+    let start: u32 = bounds.next().unwrap_or("").parse().map_err(|_| bad_region())?;
+    let end = match bounds.next() {
+        Some(end) => Some(end.parse::<u32>().map_err(|_| bad_region())?),
+        None => None,
+    };
+    if start == 0 || end.is_some_and(|end| end < start) { return Err(bad_region()); }
+    Ok((contig.to_string(), start - 1, end))
+}
+
+pub struct BamPairs<'a> {
+    reader: &'a mut BamReader,
+}
+
+impl<'a> Iterator for BamPairs<'a> {
+    type Item = (DnaRecord, DnaRecord);
+    fn next(&mut self) -> Option<(DnaRecord, DnaRecord)> {
+        let skip_flags = self.reader.skip_flags | BAM_FSECONDARY | BAM_FSUPPLEMENTARY;
+        let rec1 = self.reader.read_next(skip_flags)?;
+        let rec2 = match self.reader.read_next(skip_flags) {
+            Some(rec2) => rec2,
+            None => panic!("read {} has no mate at the end of the bam", rec1.name),
+        };
+        assert!(rec1.name == rec2.name, "read {} has no mate next to it, {} follows it", rec1.name, rec2.name);
+        if rec2.flags.unwrap_or(0) & BAM_FREAD1 != 0 { Some((rec2, rec1)) } else { Some((rec1, rec2)) }
+    }
 }
 
 impl DnaRead for BamReader {
     fn next(&mut self) -> Option<DnaRecord> {
-        let mut record = bam::record::Record::new();
-		match self.reader.read(&mut record) {
-            Err(bam::ReadError::NoMoreRecord) => return None,
-            Ok(_x) => (),//Some(Ok(x)),
-            Err(_err) => panic!("bam error, im lazy and cant be bothered to make good error messages"),
+        let skip_flags = self.skip_flags;
+        self.read_next(skip_flags)
+    }
+    fn fetch(&mut self, region: &str) -> Result<(), DnaError> { BamReader::fetch(self, region) }
+    fn count_records(&mut self) -> u64 {
+        match self.indexed {
+            Some(ref mut indexed) => count_bam_records(indexed, self.skip_flags),
+            None => count_bam_records(&mut self.reader, self.skip_flags),
         }
-        Some(DnaRecord{ 
-            name: String::from_utf8_lossy(record.qname()).to_string(), 
-            qual: Some(String::from_utf8_lossy(record.qual()).to_string()),
-            seq: String::from_utf8_lossy(&record.seq().as_bytes()).to_string(),
-        })
-            
     }
     fn my_type(&self) -> DnaFormat { Bam }
     fn header(&self) -> Option<bam::Header> { Some(bam::Header::from_template(self.reader.header())) }
     fn extension(&self) -> String { ".bam".to_string() }
+    // a fetch doesnt survive this, the whole file is read again
+    fn reset(&mut self) -> Result<(), DnaError> {
+        let path = match self.path {
+            Some(ref path) => path.clone(),
+            None => return Err(DnaError::Unsupported("cant go back to the start of a bam read from stdin".to_string())),
+        };
+        let skip_flags = self.skip_flags;
+        let tags = self.tags.clone();
+        let original_orientation = self.original_orientation;
+        *self = BamReader::new(&path)?;
+        self.skip_flags = skip_flags;
+        self.tags = tags;
+        self.original_orientation = original_orientation;
+        Ok(())
+    }
+}
+
+// shared by bam and cram, htslib reads both through bam::Reader
+fn read_bam_record<R: bam::Read>(reader: &mut R, skip_flags: u16, tags: &[String]) -> Option<DnaRecord> {
+    let mut record = bam::record::Record::new();
+    loop {
+        match reader.read(&mut record) {
+            Err(bam::ReadError::NoMoreRecord) => return None,
+            Ok(_x) => (),//Some(Ok(x)),
+            Err(_err) => panic!("bam error, im lazy and cant be bothered to make good error messages"),
+        }
+        if record.flags() & skip_flags == 0 { break; }
+    }
+    Some(DnaRecord{ 
+        name: String::from_utf8_lossy(record.qname()).to_string(), 
+        qual: bam_qual_to_string(record.qual()),
+        seq: String::from_utf8_lossy(&record.seq().as_bytes()).to_string(),
+        flags: Some(record.flags()),
+        tid: Some(record.tid()),
+        pos: Some(record.pos() as i64),
+        mapq: Some(record.mapq()),
+        tags: if tags.is_empty() { None } else { Some(bam_tags(&record, tags)) },
+        ..Default::default()
+    })
+}
+
+fn unreverse(rec: DnaRecord) -> DnaRecord {
+    if rec.flags.unwrap_or(0) & BAM_FREVERSE != 0 { rec.reverse_complement() } else { rec }
+}
+
+fn bam_tags(record: &bam::Record, tags: &[String]) -> HashMap<String, String> {
+    let mut to_ret = HashMap::new();
+    for tag in tags {
+        let value = match record.aux(tag.as_bytes()) {
+            Some(bam::record::Aux::String(x)) => String::from_utf8_lossy(x).to_string(),
+            Some(bam::record::Aux::Integer(x)) => x.to_string(),
+            Some(bam::record::Aux::Float(x)) => x.to_string(),
+            Some(bam::record::Aux::Char(x)) => (x as char).to_string(),
+            None => continue,
+        };
+        to_ret.insert(tag.clone(), value);
+    }
+    to_ret
+}
+
+fn count_bam_records<R: bam::Read>(reader: &mut R, skip_flags: u16) -> u64 {
+    let mut record = bam::record::Record::new();
+    let mut count = 0;
+    loop {
+        match reader.read(&mut record) {
+            Err(bam::ReadError::NoMoreRecord) => return count,
+            Ok(_) => if record.flags() & skip_flags == 0 { count += 1; },
+            Err(_err) => panic!("bam error while counting records"),
+        }
+    }
 }
 
 impl DnaWrite for BamWriter {
 	fn write(&mut self, rec: &DnaRecord) -> Result<(), Error> {
-		panic!("unimplemented");
+        let bam_rec = unmapped_bam_record(rec)?;
+        match self.writer.write(&bam_rec) {
+            Ok(_) => { self.records += 1; Ok(()) },
+            Err(err) => Err(Error::other(format!("bam write error on record {}: {}", rec.name, err))),
+        }
 	}
+    // htslib only writes out the last block and EOF on close, so flush finishes the file. writing after that is an error
+    fn flush(&mut self) -> Result<(), Error> { self.writer.close() }
+    fn records_written(&self) -> u64 { self.records }
+}
+
+// we dont carry alignments around so everything goes out unmapped with a * cigar
+// read names are stored with a u8 length that counts the trailing nul, so 254 is as long as they go
+// (rust_htslib asserts on longer ones)
+const BAM_MAX_QNAME: usize = 254;
+
+fn unmapped_bam_record(rec: &DnaRecord) -> Result<bam::Record, Error> {
+    if rec.name.len() > BAM_MAX_QNAME {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("record {}... has a {} byte name, bam allows at most {}",
+            &rec.name[..rec.name.char_indices().nth(20).map_or(rec.name.len(), |(i, _)| i)], rec.name.len(), BAM_MAX_QNAME)));
+    }
+    match rec.qual {
+        Some(ref qual) if qual.len() != rec.seq.len() => return Err(Error::new(ErrorKind::InvalidInput,
+            format!("record {} has {} bases but {} quals", rec.name, rec.seq.len(), qual.len()))),
+        _ => (),
+    }
+    let mut bam_rec = bam::Record::new();
+    let qual = string_to_bam_qual(&rec.qual, rec.seq.len());
+    bam_rec.set(rec.name.as_bytes(), None, rec.seq.as_bytes(), &qual);
+    bam_rec.set_tid(-1);
+    bam_rec.set_pos(-1);
+    bam_rec.set_mtid(-1);
+    bam_rec.set_mpos(-1);
+    bam_rec.set_unmapped();
+    Ok(bam_rec)
+}
+
+// bam stores raw phred scores, DnaRecord keeps them as phred+33 like fastq. 0xFF means no qual in bam.
+// anything over 93 is capped at ~ so it stays printable ascii
+fn bam_qual_to_string(qual: &[u8]) -> Option<String> {
+    if qual.is_empty() || qual[0] == 0xFF { return None; }
+    Some(qual.iter().map(|&q| (std::cmp::min(q, b'~' - 33) + 33) as char).collect())
+}
+
+fn string_to_bam_qual(qual: &Option<String>, len: usize) -> Vec<u8> {
+    match *qual {
+        Some(ref x) => x.bytes().map(|q| q.saturating_sub(33)).collect(),
+        None => vec![0xFF; len],
+    }
+}
+
+pub struct CramReader {
+    pub reader: bam::Reader,
+}
+
+impl CramReader {
+    fn new(filename: &str) -> Result<Self, Error> {
+        File::open(filename)?;
+        match bam::Reader::from_path(filename) {
+            Ok(cram) => Ok(CramReader { reader: cram }),
+            Err(err) => Err(Error::new(ErrorKind::InvalidData, format!("could not open file for cram reading: {}", err))),
+        }
+    }
+    // cram is reference compressed, htslib needs the fasta (with a .fai next to it) to decode
+    pub fn with_reference(filename: &str, ref_path: &str) -> Self {
+        let mut cram = CramReader::new(filename).expect("could not open file for cram reading");
+        cram.reader.set_reference(ref_path).expect("could not set cram reference, is there a .fai for it?");
+        cram
+    }
+}
+
+impl DnaRead for CramReader {
+    fn next(&mut self) -> Option<DnaRecord> { read_bam_record(&mut self.reader, 0, &[]) }
+    fn my_type(&self) -> DnaFormat { Cram }
+    fn header(&self) -> Option<bam::Header> { Some(bam::Header::from_template(self.reader.header())) }
+    fn extension(&self) -> String { ".cram".to_string() }
+}
+
+// unaligned cram, eg for archiving fastq. with no @SQ lines htslib stores the bases themselves, no reference needed
+pub struct CramWriter {
+    writer: HtsWriter,
+    records: u64,
+}
+
+impl CramWriter {
+    fn new(filename: &str, template: &DnaReader) -> Self {
+        let header = template.header().unwrap_or_else(minimal_sam_header);
+        CramWriter::from_header(filename, &header)
+    }
+    pub fn from_header(filename: &str, header: &bam::Header) -> Self {
+        CramWriter::try_from_header(filename, header).expect("could not open cram for writing")
+    }
+    pub fn try_from_header(filename: &str, header: &bam::Header) -> Result<Self, Error> {
+        Ok(CramWriter{ writer: HtsWriter::open(filename, header, "wc", None)?, records: 0 })
+    }
+    // headers with @SQ lines need the reference those contigs came from
+    pub fn with_reference(filename: &str, header: &bam::Header, ref_path: &str) -> Self {
+        let writer = HtsWriter::open(filename, header, "wc", Some(ref_path)).expect("could not open cram for writing");
+        CramWriter{ writer, records: 0 }
+    }
+}
+
+impl DnaWrite for CramWriter {
+    fn write(&mut self, rec: &DnaRecord) -> Result<(), Error> {
+        let bam_rec = unmapped_bam_record(rec)?;
+        match self.writer.write(&bam_rec) {
+            Ok(_) => { self.records += 1; Ok(()) },
+            Err(err) => Err(Error::other(format!("cram write error on record {}: {}", rec.name, err))),
+        }
+    }
+    // same as bam, the last container only goes out on close so this finishes the file
+    fn flush(&mut self) -> Result<(), Error> { self.writer.close() }
+    fn records_written(&self) -> u64 { self.records }
+}
+
+// just @HD VN:1.6 SO:unknown, enough for unmapped records. eg for writing fastq reads to sam/bam
+pub fn minimal_sam_header() -> bam::Header {
+    let mut header = bam::Header::new();
+    let mut hd = bam::header::HeaderRecord::new(b"HD");
+    hd.push_tag(b"VN", &"1.6").push_tag(b"SO", &"unknown");
+    header.push_record(&hd);
+    header
 }
 
 pub struct SamReader {
     buf_reader: BufReader<Box<std::io::Read>>,
+    compression: Compression,
+    path: Option<String>,
+    header: Option<bam::Header>,
 }
 
 pub struct SamWriter {
-	writer: sam::Writer,
+	writer: HtsWriter,
+    records: u64,
 }
 
 impl SamReader {
-    fn new(filename: &str) -> Self {
-        let reader = get_reader(filename, Uncompressed);
-        SamReader{ buf_reader: reader }
+    fn new(filename: &str, compression: Compression) -> Result<Self, Error> {
+        let mut reader = SamReader::from_read(open_file(filename)?, compression);
+        reader.path = Some(filename.to_string());
+        Ok(reader)
+    }
+    fn from_read(source: Box<std::io::Read>, compression: Compression) -> Self {
+        let mut reader = get_reader(source, compression.clone(), DEFAULT_BUFFER_SIZE);
+        let header = read_sam_header(&mut reader);
+        SamReader{ buf_reader: reader, compression, path: None, header }
+    }
+}
+
+// eats the @ lines off the front of a sam so header() can hand them back for sam to sam round trips
+fn read_sam_header(reader: &mut BufReader<Box<std::io::Read>>) -> Option<bam::Header> {
+    let mut header = bam::Header::new();
+    let mut lines = 0;
+    while let Some(b'@') = reader.fill_buf().expect("Could not read file").first() {
+        let mut line = String::new();
+        reader.read_line(&mut line).expect("Could not read file");
+        let line = line.trim_end_matches(['\n', '\r']);
+        let mut fields = line[1..].split('\t');
+        let rec_type = fields.next().unwrap_or("");
+        if rec_type == "CO" {
+            header.push_comment(line.split_once('\t').map(|x| x.1).unwrap_or("").as_bytes());
+        } else {
+            let mut rec = bam::header::HeaderRecord::new(rec_type.as_bytes());
+            for field in fields {
+                let mut tag_value = field.splitn(2, ':');
+                let tag = tag_value.next().unwrap_or("");
+                rec.push_tag(tag.as_bytes(), &tag_value.next().unwrap_or(""));
+            }
+            header.push_record(&rec);
+        }
+        lines += 1;
     }
+    match lines { 0 => None, _ => Some(header) }
 }
 
 impl SamWriter {
@@ -400,54 +3548,109 @@ impl SamWriter {
             Some(x) => x,
             None => panic!("i have no header for template"),
         };
-		let writer = sam::Writer::from_path(filename, &header).expect("could not open sam file for writing");
-        SamWriter{ writer: writer }
+		SamWriter::from_header(filename, &header)
 	}
+    pub fn from_header(filename: &str, header: &bam::Header) -> Self {
+        SamWriter::try_from_header(filename, header).expect("could not open sam file for writing")
+    }
+    pub fn try_from_header(filename: &str, header: &bam::Header) -> Result<Self, Error> {
+        Ok(SamWriter{ writer: HtsWriter::open(filename, header, "w", None)?, records: 0 })
+    }
 }
 
 impl DnaRead for SamReader {
     fn next(&mut self) -> Option<DnaRecord> {
         let mut line = String::new();
-        let mut to_ret: Option<DnaRecord> = None;
-        while !self.buf_reader.read_line(&mut line).unwrap_or(0) > 0 && !line.starts_with("@") {
-            let line: Vec<&str> = line.split_whitespace().collect();
-            assert!( line.len() > 10, "is this sam format?, error parsing");
-            to_ret = Some( DnaRecord{ name: line[0].to_string(), seq: line[9].to_string(), qual: Some(line[10].to_string()) } )
-        };
-        to_ret
+        loop {
+            line.clear();
+            if self.buf_reader.read_line(&mut line).expect("Could not read file") == 0 { return None; }
+            if line.starts_with("@") || line.trim().is_empty() { continue; }
+            let fields: Vec<&str> = line.trim_end_matches(['\n', '\r']).split('\t').collect();
+            assert!( fields.len() > 10, "is this sam format?, error parsing");
+            let qual = match fields[10] {
+                "*" => None,
+                x => Some(x.to_string()),
+            };
+            return Some( DnaRecord{ name: fields[0].to_string(), seq: fields[9].to_string(), qual, ..Default::default() } );
+        }
     }
     fn my_type(&self) -> DnaFormat { Sam }
-    fn header(&self) -> Option<bam::Header> { None }
-    fn extension(&self) -> String { ".sam".to_string() }
+    fn header(&self) -> Option<bam::Header> { self.header.clone() }
+    fn extension(&self) -> String {
+        let mut to_ret = ".sam".to_string();
+        to_ret.push_str(compression_extension(&self.compression));
+        to_ret
+    }
+    fn reset(&mut self) -> Result<(), DnaError> {
+        self.buf_reader = reopen_reader(&self.path, &self.compression, DEFAULT_BUFFER_SIZE)?;
+        Ok(())
+    }
 }
 
 impl DnaWrite for SamWriter {
 	fn write(&mut self, rec: &DnaRecord) -> Result<(), Error> {
-		panic!("unimplemented");
-        let mut bam_rec = bam::Record::new();
-        let qual = match rec.qual {
-            Some(ref x) => x,
-            None => panic!("what am i doing writing sam, i have no qual"),
-        };
-        bam_rec.set(&rec.name.as_bytes(), Some(&bam::record::CigarString::from_str("").unwrap()), &rec.seq.as_bytes(), &qual.as_bytes());
+        let bam_rec = unmapped_bam_record(rec)?;
         match self.writer.write(&bam_rec) {
-            Ok(_) => Ok(()),
-            Err(_) => panic!("write error on sam write"),
+            Ok(_) => { self.records += 1; Ok(()) },
+            Err(err) => Err(Error::other(format!("sam write error on record {}: {}", rec.name, err))),
         }
 	}
+    // same as bam, htslib buffers until close so this finishes the file
+    fn flush(&mut self) -> Result<(), Error> { self.writer.close() }
+    fn records_written(&self) -> u64 { self.records }
 }
 
+#[cfg(test)]
 mod tests {
-    #[allow(unused_imports)]
     use DnaReader;
-    #[allow(unused_imports)]
+    use DnaReaderBuilder;
+    use DnaWriterBuilder;
     use DnaRecord;
-    #[allow(unused_imports)]
+    use BaseStats;
     use DnaWriter;
     use DnaWrite;
+    use BamWriter;
+    use SamWriter;
+    use CramWriter;
+    use FastaWriter;
+    use PairedFastqReader;
+    use InterleavedFastqWriter;
+    use ChunkedFastqWriter;
+    use TeeWriter;
+    use FastqReader;
+    use FastqWriter;
+    use BgzfFastqReader;
+    use BgzfFastqWriter;
+    use FastaReader;
+    use FastaWindow;
+    use TwoBitReader;
+    use BamReader;
+    use DnaRead;
     use std::io::Read;
+    use std::io::Cursor;
+    use std::io::ErrorKind;
+    use DnaError;
+    use DnaFormat;
+    use BGZF_EOF;
+    use DEFAULT_BUFFER_SIZE;
+    use parse_region;
+    use bam_qual_to_string;
+    use unmapped_bam_record;
+    use check_extension;
+    use pair_stem;
+    use rust_htslib::bam;
+    use DnaFormat::*;
+    use Compression::*;
     use std::fs::File;
     use flush;
+    use convert;
+    use convert_lossy;
+    use minimal_sam_header;
+    use build_fai;
+    use extract_bed;
+    use split_fasta;
+    use std::collections::HashSet;
+    use IndexedFastaReader;
 
     #[test]
     fn test_fastq() {
@@ -489,6 +3692,867 @@ mod tests {
         assert!("ACTGGTCA"==rec.seq); 
     }
 
+    #[test]
+    fn test_fastq_from_read() {
+        let data = b"@pewpew\nACTGGTCA\n+\n++++++++\n".to_vec();
+        let mut reader = DnaReader::from_read(Box::new(Cursor::new(data)), Fastq, Uncompressed);
+        let rec = reader.next().expect("no records from cursor");
+        assert!("ACTGGTCA" == rec.seq);
+        assert!(reader.next().is_none());
+
+        let mut gz = Vec::new();
+        File::open("test/data/fastq.fastq.gz").expect("test data not available").read_to_end(&mut gz).expect("cant read test data");
+        let reader = DnaReader::from_read(Box::new(Cursor::new(gz)), Fastq, Gzipped);
+        assert!(reader.count() == 2);
+    }
+
+    #[test]
+    fn test_bzip2_fasta() {
+        let reader = DnaReader::from_path("test/data/fasta.fasta.bz2");
+        assert!(reader.extension() == ".fasta.bz2");
+        let mut writer = DnaWriter::from_path("test/data/fasta_written.fasta.bz2");
+        for rec in reader {
+            writer.write(&rec).expect("failed to write bzip2 fasta file in test");
+        }
+        flush(writer).expect("failed to flush writer in test");
+        let original = DnaReader::from_path("test/data/fasta.fasta");
+        let written = DnaReader::from_path("test/data/fasta_written.fasta.bz2");
+        let mut count = 0;
+        for (rec1, rec2) in original.zip(written) {
+            assert!(rec1.name == rec2.name);
+            assert!(rec1.seq == rec2.seq);
+            count += 1;
+        }
+        assert!(count == 2);
+    }
+
+    #[test]
+    fn test_zstd_fastq() {
+        let reader = DnaReader::from_path("test/data/fastq.fastq");
+        let mut writer = DnaWriter::from_path("test/data/fastq_written.fastq.zst");
+        for rec in reader {
+            writer.write(&rec).expect("failed to write zstd fastq file in test");
+        }
+        flush(writer).expect("failed to flush writer in test");
+        let reader = DnaReader::from_path("test/data/fastq.fastq");
+        let written = DnaReader::from_path("test/data/fastq_written.fastq.zst");
+        assert!(written.extension() == ".fastq.zst");
+        let mut count = 0;
+        for (rec1, rec2) in reader.zip(written) {
+            assert!(rec1.name == rec2.name);
+            assert!(rec1.seq == rec2.seq);
+            assert!(rec1.qual == rec2.qual);
+            count += 1;
+        }
+        assert!(count == 2);
+    }
+
+    #[test]
+    fn test_autodetect() {
+        let mut reader = DnaReader::from_path_autodetect("test/data/reads.txt").expect("could not autodetect reads.txt");
+        let rec = reader.next().expect("no records in misnamed fastq");
+        assert!("ACTGGTCA" == rec.seq);
+        assert!(rec.qual.is_some());
+
+        let mut reader = DnaReader::from_path_autodetect("test/data/fasta.fasta.gz").expect("could not autodetect fasta.fasta.gz");
+        let rec = reader.next().expect("no records in gzipped fasta");
+        assert!("ACGTTTTTTTTTTTTTTACGT" == rec.seq);
+        assert!(reader.extension() == ".fasta.gz");
+
+        let reader = DnaReader::from_path_autodetect("test/data/test.sam").expect("could not autodetect test.sam");
+        assert!(reader.extension() == ".sam");
+
+        // gzipped with nothing in the name to go on, the format comes from the decompressed bytes
+        let mut reader = DnaReader::from_path_autodetect("test/data/gzipped_fastq.dat").expect("could not autodetect gzipped_fastq.dat");
+        assert!(reader.my_type() == Fastq && reader.extension() == ".fastq.gz");
+        let recs: Vec<DnaRecord> = reader.by_ref().collect();
+        assert!(recs.len() == 2 && recs[0].name == "pewpew" && recs[0].seq == "ACTGGTCA");
+        let mut reader = DnaReader::from_path_autodetect("test/data/gzipped_fasta.dat").expect("could not autodetect gzipped_fasta.dat");
+        assert!(reader.my_type() == Fasta);
+        assert!(reader.next().expect("no records in gzipped fasta").seq == "ACGTTTTTTTTTTTTTTACGT");
+
+        // 2bit has no magic we sniff for so the extension decides, and it cant be read as a stream
+        let reader = DnaReader::from_path_autodetect("test/data/test.2bit").expect("could not autodetect test.2bit");
+        assert!(reader.my_type() == TwoBit && reader.count() == 2);
+
+        match DnaReader::from_path_autodetect("test/data/unknown.dat") {
+            Err(DnaError::UnknownExtension(_)) => (),
+            _ => panic!("text with no known extension should be an unknown extension"),
+        }
+        match DnaReader::from_path_autodetect("test/data/not_there.fastq") {
+            Err(DnaError::Io(ref err)) if err.kind() == ErrorKind::NotFound => (),
+            _ => panic!("missing file should be a not found io error"),
+        }
+    }
+
+    #[test]
+    fn test_reverse_complement() {
+        let rec = DnaRecord{ name: "rc".to_string(), seq: "ACGTNacgtn".to_string(), qual: Some("ABCDEFGHIJ".to_string()), ..Default::default() };
+        let rc = rec.reverse_complement();
+        assert!(rc.seq == "nacgtNACGT");
+        assert!(rc.qual == Some("JIHGFEDCBA".to_string()));
+        assert!(rc.name == "rc");
+
+        let rec = DnaRecord{ name: "iupac".to_string(), seq: "RYSWKMBDHV".to_string(), qual: None, ..Default::default() };
+        let rc = rec.reverse_complement();
+        assert!(rc.seq == "BDHVKMWSRY");
+        assert!(rc.qual.is_none());
+    }
+
+    #[test]
+    fn test_trim_quality() {
+        // quals 40 40 40 40 30 10 2 2
+        let rec = DnaRecord{ name: "tail".to_string(), seq: "ACGTACGT".to_string(), qual: Some("IIII?+##".to_string()), ..Default::default() };
+        let trimmed = rec.trim_quality(33, 20);
+        assert!(trimmed.seq == "ACGTA");
+        assert!(trimmed.qual == Some("IIII?".to_string()));
+        assert!(trimmed.name == "tail");
+        let good = rec.trim_quality(33, 1);
+        assert!(good.seq == rec.seq);
+        let bad = rec.trim_quality(33, 60);
+        assert!(bad.is_empty());
+        let fasta = DnaRecord{ name: "blah".to_string(), seq: "ACGT".to_string(), qual: None, ..Default::default() };
+        assert!(fasta.trim_quality(33, 20).seq == "ACGT");
+    }
+
+    #[test]
+    fn test_slice_short_qual() {
+        let rec = DnaRecord{ name: "short".to_string(), seq: "ACGTACGT".to_string(), qual: Some("IIII".to_string()), ..Default::default() };
+        assert!(rec.trim_ns() == rec);
+        let trimmed = rec.trim_quality(33, 20);
+        assert!(trimmed.seq == "ACGT" && trimmed.qual == Some("IIII".to_string()));
+        let fitted = rec.fit_to_length(6, b'N', b'#');
+        assert!(fitted.seq == "ACGTAC" && fitted.qual == Some("IIII".to_string()));
+        assert!(rec.sliding_window_trim(33, 4, 20).seq == "ACGT");
+        let long = DnaRecord{ name: "long".to_string(), seq: "AC".to_string(), qual: Some("IIII".to_string()), ..Default::default() };
+        assert!(long.trim_quality(33, 20).seq == "AC");
+    }
+
+    #[test]
+    fn test_sliding_window_trim() {
+        // quals 40 40 40 40 10 10 10 40 40 40, dips in the middle
+        let rec = DnaRecord{ name: "dip".to_string(), seq: "ACGTACGTAC".to_string(), qual: Some("IIII+++III".to_string()), ..Default::default() };
+        let trimmed = rec.sliding_window_trim(33, 4, 30);
+        // window at 2 averages 25
+        assert!(trimmed.seq == "AC" && trimmed.qual == Some("II".to_string()) && trimmed.name == "dip");
+        assert!(rec.sliding_window_trim(33, 4, 10) == rec);
+        assert!(rec.sliding_window_trim(33, 1, 20).seq == "ACGT");
+        // shorter than the window so the whole read averages 31
+        assert!(rec.sliding_window_trim(33, 20, 31).seq == "ACGTACGTAC");
+        assert!(rec.sliding_window_trim(33, 20, 32).is_empty());
+        let fasta = DnaRecord{ name: "fasta".to_string(), seq: "ACGT".to_string(), qual: None, ..Default::default() };
+        assert!(fasta.sliding_window_trim(33, 4, 20) == fasta);
+    }
+
+    #[test]
+    fn test_decode_colorspace() {
+        let rec = DnaRecord{ name: "solid".to_string(), seq: "T0123".to_string(), qual: Some("IIII".to_string()), ..Default::default() };
+        let decoded = rec.decode_colorspace().expect("is colorspace");
+        assert!(decoded.seq == "TGAT" && decoded.qual == Some("IIII".to_string()) && decoded.name == "solid");
+        // a qual for the primer too
+        let rec = DnaRecord{ name: "solid2".to_string(), seq: "G3300121".to_string(), qual: Some("!ABCDEFG".to_string()), ..Default::default() };
+        let decoded = rec.decode_colorspace().expect("is colorspace");
+        assert!(decoded.seq == "CGGGTCA" && decoded.qual == Some("ABCDEFG".to_string()));
+        let missing = DnaRecord{ name: "missing".to_string(), seq: "A01.23".to_string(), qual: None, ..Default::default() };
+        assert!(missing.decode_colorspace().expect("is colorspace").seq == "ACNNN");
+        let bases = DnaRecord{ name: "bases".to_string(), seq: "ACGT".to_string(), qual: None, ..Default::default() };
+        assert!(bases.decode_colorspace().is_none());
+        let digits = DnaRecord{ name: "digits".to_string(), seq: "0123".to_string(), qual: None, ..Default::default() };
+        assert!(digits.decode_colorspace().is_none());
+    }
+
+    #[test]
+    fn test_fit_to_length() {
+        let short = DnaRecord{ name: "short".to_string(), seq: "ACG".to_string(), qual: Some("III".to_string()), ..Default::default() };
+        let padded = short.fit_to_length(6, b'N', b'!');
+        assert!(padded.seq == "ACGNNN" && padded.qual == Some("III!!!".to_string()) && padded.name == "short");
+        let long = DnaRecord{ name: "long".to_string(), seq: "ACGTACGT".to_string(), qual: Some("ABCDEFGH".to_string()), ..Default::default() };
+        let truncated = long.fit_to_length(6, b'N', b'!');
+        assert!(truncated.seq == "ACGTAC" && truncated.qual == Some("ABCDEF".to_string()));
+        assert!(long.fit_to_length(8, b'N', b'!') == long);
+        let fasta = DnaRecord{ name: "fasta".to_string(), seq: "AC".to_string(), qual: None, ..Default::default() };
+        let padded = fasta.fit_to_length(4, b'N', b'!');
+        assert!(padded.seq == "ACNN" && padded.qual.is_none());
+    }
+
+    #[test]
+    fn test_trim_ns() {
+        let rec = DnaRecord{ name: "ends".to_string(), seq: "NNACNGTnn".to_string(), qual: Some("!!IIIII##".to_string()), ..Default::default() };
+        let trimmed = rec.trim_ns();
+        assert!(trimmed.seq == "ACNGT" && trimmed.qual == Some("IIIII".to_string()) && trimmed.name == "ends");
+        let clean = DnaRecord{ name: "clean".to_string(), seq: "ACGT".to_string(), qual: Some("IIII".to_string()), ..Default::default() };
+        assert!(clean.trim_ns() == clean);
+        let all_n = DnaRecord{ name: "all_n".to_string(), seq: "NNNN".to_string(), qual: Some("!!!!".to_string()), ..Default::default() };
+        assert!(all_n.trim_ns().is_empty() && all_n.trim_ns().qual == Some(String::new()));
+        let fasta = DnaRecord{ name: "fasta".to_string(), seq: "nACGTN".to_string(), qual: None, ..Default::default() };
+        assert!(fasta.trim_ns().seq == "ACGT" && fasta.trim_ns().qual.is_none());
+    }
+
+    #[test]
+    fn test_masking() {
+        let rec = DnaReader::from_path("test/data/softmasked.fasta").next().expect("no soft masked record");
+        assert!(rec.seq == "ACGTacgt");
+        let hard = rec.hard_mask();
+        assert!(hard.seq == "ACGTNNNN");
+        assert!(hard.name == "masked");
+        assert!(rec.uppercase().seq == "ACGTACGT");
+        let fastq = DnaRecord{ name: "r".to_string(), seq: "acGT".to_string(), qual: Some("!#%'".to_string()), ..Default::default() };
+        assert!(fastq.hard_mask().seq == "NNGT");
+        assert!(fastq.hard_mask().qual == fastq.qual);
+        assert!(fastq.uppercase().qual == fastq.qual);
+    }
+
+    #[test]
+    fn test_gc_content() {
+        let rec = DnaRecord{ name: "gc".to_string(), seq: "ACgtNNGC".to_string(), qual: None, ..Default::default() };
+        assert!(rec.len() == 8);
+        assert!(rec.gc_content() == 4.0 / 6.0);
+        let rec = DnaRecord{ name: "allN".to_string(), seq: "NNNNnn".to_string(), qual: None, ..Default::default() };
+        assert!(rec.gc_content() == 0.0);
+        let rec = DnaRecord{ name: "empty".to_string(), seq: "".to_string(), qual: None, ..Default::default() };
+        assert!(rec.is_empty());
+        assert!(rec.gc_content() == 0.0);
+    }
+
+    #[test]
+    fn test_auto_extension() {
+        std::fs::create_dir_all("test/data/auto_ext_written").expect("could not make test output dir");
+        let _ = std::fs::remove_file("test/data/auto_ext_written/out.fastq.gz");
+        let reader = DnaReader::from_path("test/data/fastq.fastq.gz");
+        let mut writer = DnaWriter::from_reader_auto_extension("test/data/auto_ext_written/out", &reader);
+        for rec in reader {
+            writer.write(&rec).expect("failed to write auto extension fastq in test");
+        }
+        flush(writer).expect("failed to flush writer in test");
+        let written = DnaReader::from_path("test/data/auto_ext_written/out.fastq.gz");
+        assert!(written.extension() == ".fastq.gz");
+        assert!(written.count() == 2);
+    }
+
+    #[test]
+    fn test_path_with_format() {
+        let reader = DnaReader::from_path_with_format("test/data/reads.txt", Fastq, Uncompressed);
+        assert!(reader.my_type() == Fastq);
+        let mut writer = DnaWriter::from_path_with_format("test/data/fastq_written_format.dat", Fastq, Gzipped);
+        for rec in reader {
+            writer.write(&rec).expect("failed to write mis-extensioned fastq in test");
+        }
+        flush(writer).expect("failed to flush writer in test");
+        let written = DnaReader::from_path_with_format("test/data/fastq_written_format.dat", Fastq, Gzipped);
+        let original = DnaReader::from_path("test/data/fastq.fastq");
+        let mut count = 0;
+        for (rec1, rec2) in original.zip(written) {
+            assert!(rec1.name == rec2.name);
+            assert!(rec1.qual == rec2.qual);
+            count += 1;
+        }
+        assert!(count == 2);
+    }
+
+    #[test]
+    fn test_try_from_path() {
+        match DnaReader::try_from_path("test/data/fastq.fastq") {
+            Ok(mut reader) => assert!(reader.next().is_some()),
+            Err(err) => panic!("should have opened: {}", err),
+        }
+        match DnaReader::try_from_path("test/data/fastq.unknown") {
+            Err(DnaError::UnknownExtension(_)) => (),
+            _ => panic!("expected an unknown extension error"),
+        }
+        match DnaReader::try_from_path("test/data/does_not_exist.fastq") {
+            Err(DnaError::Io(ref err)) => assert!(err.kind() == ErrorKind::NotFound),
+            _ => panic!("expected an io error for a missing file"),
+        }
+        match DnaReader::try_from_path("test/data/does_not_exist.2Bit") {
+            Err(DnaError::Io(ref err)) => assert!(err.kind() == ErrorKind::NotFound),
+            _ => panic!("expected an io error for a missing 2bit file"),
+        }
+    }
+
+    #[test]
+    fn test_fasta_blank_line() {
+        let reader = DnaReader::from_path("test/data/blank_line.fasta");
+        let recs: Vec<DnaRecord> = reader.collect();
+        assert!(recs.len() == 2);
+        assert!(recs[0].seq == "ACGTACGT");
+        let mut reader = FastaReader::new("test/data/fasta.fasta", Uncompressed).expect("test data not available");
+        reader.strict = true;
+        reader.allow_lowercase = false;
+        assert!(reader.next().is_some());
+    }
+
+    #[test]
+    fn test_fasta_blank_line_strict() {
+        let mut reader = FastaReader::new("test/data/blank_line.fasta", Uncompressed).expect("test data not available");
+        reader.strict = true;
+        match reader.try_next() {
+            Err(DnaError::Parse(msg)) => assert!(msg.contains("blank line")),
+            _ => panic!("blank line in a strict fasta should be a parse error"),
+        }
+        // the bad record is skipped over, not left half read
+        assert!(reader.try_next().expect("second record is fine").is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "blank line inside fasta record")]
+    fn test_fasta_blank_line_strict_next() {
+        let mut reader = FastaReader::new("test/data/blank_line.fasta", Uncompressed).expect("test data not available");
+        reader.strict = true;
+        reader.next();
+    }
+
+    #[test]
+    fn test_fasta_lowercase_strict() {
+        let mut reader = FastaReader::new("test/data/softmasked.fasta", Uncompressed).expect("test data not available");
+        reader.strict = true;
+        reader.allow_lowercase = false;
+        match reader.try_next() {
+            Err(DnaError::Parse(msg)) => assert!(msg.contains("lowercase")),
+            _ => panic!("lowercase bases in a strict fasta should be a parse error"),
+        }
+    }
+
+    #[test]
+    fn test_two_bit() {
+        let reader = DnaReader::from_path("test/data/test.2bit");
+        assert!(reader.extension() == ".2bit");
+        let recs: Vec<DnaRecord> = reader.collect();
+        assert!(recs.len() == 2);
+        assert!(recs[0].name == "chr1");
+        assert!(recs[0].seq == "ACGTNNNNacgtTTGCA");
+        assert!(recs[0].qual.is_none());
+        assert!(recs[1].name == "chr2");
+        assert!(recs[1].seq == "GGGAT");
+        assert!(DnaReader::from_path("test/data/test.2bit").count_records() == 2);
+        assert!(TwoBitReader::new("test/data/fasta.fasta").is_err());
+    }
+
+    // counts how many times the reader goes back to the source
+    struct CountingRead {
+        inner: File,
+        reads: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+
+    impl Read for CountingRead {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.reads.set(self.reads.get() + 1);
+            self.inner.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_buffer_size() {
+        let mut counts = Vec::new();
+        for size in [8 * 1024, DEFAULT_BUFFER_SIZE].iter() {
+            let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+            let source = CountingRead{ inner: File::open("test/data/many.fasta").expect("test data not available"), reads: reads.clone() };
+            let reader = FastaReader::from_read_with_buffer_size(Box::new(source), Uncompressed, *size);
+            assert!(DnaReader::new(Box::new(reader)).count() == 200);
+            counts.push(reads.get());
+        }
+        assert!(counts[1] < counts[0]);
+        assert!(DnaReader::from_path_with_buffer_size("test/data/fastq.fastq", 1024 * 1024).count() == 2);
+    }
+
+    #[test]
+    fn test_raw() {
+        let recs: Vec<DnaRecord> = DnaReader::from_path("test/data/kmers.txt").collect();
+        assert!(recs.len() == 3);
+        assert!(recs[0].name == "seq_0" && recs[0].seq == "ACGTA");
+        assert!(recs[2].name == "seq_2" && recs[2].seq == "TTTTT");
+        assert!(recs[1].qual.is_none());
+        let mut writer = DnaWriter::from_path("test/data/kmers_written.txt");
+        for rec in DnaReader::from_path("test/data/fastq.fastq") {
+            writer.write(&rec).expect("failed to write raw in test");
+        }
+        flush(writer).expect("failed to flush writer in test");
+        let mut contents = String::new();
+        File::open("test/data/kmers_written.txt").expect("written test data not available").read_to_string(&mut contents).expect("cant read written test data");
+        assert!(contents == "ACTGGTCA\nTTTTTTTT\n");
+        let written: Vec<DnaRecord> = DnaReader::from_path("test/data/kmers_written.txt").collect();
+        assert!(written[1].seq == "TTTTTTTT");
+        assert!(DnaReader::from_path("test/data/kmers.txt").count_records() == 3);
+    }
+
+    #[test]
+    fn test_tsv() {
+        convert("test/data/fastq.fastq", "test/data/fastq_written_tsv.tsv").expect("fastq to tsv failed in test");
+        let mut contents = String::new();
+        File::open("test/data/fastq_written_tsv.tsv").expect("written test data not available").read_to_string(&mut contents).expect("cant read written test data");
+        assert!(contents.lines().all(|line| line.split('\t').count() == 3));
+        convert("test/data/fastq_written_tsv.tsv", "test/data/fastq_written_tsv.fastq").expect("tsv to fastq failed in test");
+        let original: Vec<DnaRecord> = DnaReader::from_path("test/data/fastq.fastq").collect();
+        let round_trip: Vec<DnaRecord> = DnaReader::from_path("test/data/fastq_written_tsv.fastq").collect();
+        assert!(original.len() == 2 && original == round_trip);
+        // no qual column for fasta
+        let mut writer = DnaWriter::from_path("test/data/fasta_written_tsv.tsv");
+        writer.write_all(DnaReader::from_path("test/data/fasta.fasta")).expect("failed to write tsv in test");
+        flush(writer).expect("failed to flush writer in test");
+        let recs: Vec<DnaRecord> = DnaReader::from_path("test/data/fasta_written_tsv.tsv").collect();
+        assert!(!recs.is_empty() && recs.iter().all(|rec| rec.qual.is_none()));
+        assert!(DnaReader::from_path("test/data/fasta_written_tsv.tsv").count_records() == recs.len() as u64);
+    }
+
+    #[test]
+    fn test_count_records() {
+        for path in ["test/data/fastq.fastq", "test/data/fasta.fasta", "test/data/many.fasta", "test/data/test.sam", "test/data/test.bam"].iter() {
+            let mut reader = DnaReader::from_path(path);
+            let counted = reader.count_records();
+            assert!(reader.next().is_none());
+            assert!(counted == DnaReader::from_path(path).count() as u64);
+        }
+        let mut reader = DnaReader::from_path("test/data/many.fasta");
+        reader.next();
+        reader.peek();
+        assert!(reader.count_records() == 199);
+    }
+
+    #[test]
+    fn test_flush_gz() {
+        let mut writer = DnaWriter::from_path("test/data/fastq_written_flush.fastq.gz");
+        for rec in DnaReader::from_path("test/data/fastq.fastq") {
+            writer.write(&rec).expect("failed to write gz fastq file in test");
+        }
+        writer.flush().expect("failed to flush gz writer in test");
+        // writer is still alive, so the trailer has to be there already
+        let reader = DnaReader::from_path("test/data/fastq_written_flush.fastq.gz");
+        assert!(reader.count() == 2);
+        for rec in DnaReader::from_path("test/data/fastq.fastq") {
+            writer.write(&rec).expect("failed to write second gzip member in test");
+        }
+        flush(writer).expect("failed to flush writer in test");
+        let reader = DnaReader::from_path("test/data/fastq_written_flush.fastq.gz");
+        assert!(reader.count() == 4);
+    }
+
+    #[test]
+    fn test_threaded_gz() {
+        let single: Vec<DnaRecord> = DnaReader::from_path("test/data/fastq.fastq.gz").collect();
+        match DnaReader::from_path_with_threads("test/data/fastq.fastq.gz", 4) {
+            Err(DnaError::Unsupported(_)) => (),
+            _ => panic!("plain gzip cant use 4 threads, that should be unsupported"),
+        }
+        match DnaReader::from_path_with_threads("test/data/fastq.fastq", 4) {
+            Err(DnaError::Unsupported(_)) => (),
+            _ => panic!("uncompressed fastq cant use 4 threads, that should be unsupported"),
+        }
+        assert!(DnaReader::from_path_with_threads("test/data/fastq.fastq", 1).expect("one thread is always fine").count() == 2);
+        match DnaReader::from_path_with_threads("test/data/fastq.fastq", 0) {
+            Err(DnaError::Unsupported(_)) => (),
+            _ => panic!("0 threads should be unsupported"),
+        }
+        let reader = DnaReader::from_path_with_threads("test/data/fastq.fastq.gz", 1).expect("plain gzip on one thread failed");
+        assert!(reader.extension() == ".fastq.gz");
+        let threaded: Vec<DnaRecord> = reader.collect();
+        assert!(single.len() == 2 && threaded.len() == 2);
+        for (rec1, rec2) in single.iter().zip(threaded.iter()) {
+            assert!(rec1.name == rec2.name && rec1.seq == rec2.seq && rec1.qual == rec2.qual);
+        }
+    }
+
+    #[test]
+    fn test_threaded_bgzf() {
+        let mut writer = DnaWriter::from_path_with_compression("test/data/many_written_bgzf.fasta.gz", Bgzf);
+        for rec in DnaReader::from_path("test/data/many.fasta") {
+            writer.write(&rec).expect("failed to write bgzf fasta in test");
+        }
+        flush(writer).expect("failed to flush writer in test");
+        let single: Vec<DnaRecord> = DnaReader::from_path("test/data/many.fasta").collect();
+        let threaded: Vec<DnaRecord> = DnaReader::from_path_with_threads("test/data/many_written_bgzf.fasta.gz", 4)
+            .expect("bgzf should split over threads").collect();
+        assert!(threaded.len() == 200);
+        for (rec1, rec2) in single.iter().zip(threaded.iter()) {
+            assert!(rec1.name == rec2.name && rec1.seq == rec2.seq);
+        }
+    }
+
+    #[test]
+    fn test_bgzf_fastq_seek() {
+        let mut writer = BgzfFastqWriter::new("test/data/seek_written.fastq.gz");
+        let mut offsets = Vec::new();
+        // enough to fill a few blocks
+        for i in 0..3000 {
+            let seq: String = (0..50).map(|j| ['A', 'C', 'G', 'T'][(i * 7 + j * 3) % 4]).collect();
+            let rec = DnaRecord{ name: format!("read{}", i), seq, qual: Some("I".repeat(50)), ..Default::default() };
+            writer.write(&rec).expect("failed to write bgzf fastq in test");
+            offsets.push(writer.last_offset().expect("no offset after a write"));
+        }
+        writer.flush().expect("failed to flush writer in test");
+        assert!(offsets.iter().any(|offset| offset >> 16 > 0));
+        // records never straddle blocks so a block start is always a record start
+        assert!(offsets.iter().filter(|&offset| offset & 0xffff == 0).count() > 1);
+        let mut reader = BgzfFastqReader::new("test/data/seek_written.fastq.gz").expect("written test data not available");
+        reader.next().expect("no first record");
+        let second = reader.virtual_offset();
+        assert!(second == offsets[1]);
+        let rec2 = reader.next().expect("no second record");
+        reader.next();
+        reader.seek(second).expect("failed to seek in test");
+        assert!(reader.next().expect("nothing after seek") == rec2);
+        for &i in [0, 1499, 2999].iter() {
+            reader.seek(offsets[i]).expect("failed to seek in test");
+            assert!(reader.next().expect("nothing after seek").name == format!("read{}", i));
+        }
+        assert!(DnaReader::from_path("test/data/seek_written.fastq.gz").count() == 3000);
+        let mut reader = BgzfFastqReader::new("test/data/fastq.fastq.gz").expect("test data not available");
+        match reader.try_next() {
+            Err(_) => (),
+            _ => panic!("plain gzip is not bgzf"),
+        }
+    }
+
+    #[test]
+    fn test_append() {
+        for filename in ["test/data/append_written.fastq", "test/data/append_written.fastq.gz"].iter() {
+            let _ = std::fs::remove_file(filename);
+            for _ in 0..2 {
+                let mut writer = DnaWriter::from_path_append(filename);
+                writer.write_all(DnaReader::from_path("test/data/fastq.fastq")).expect("failed to append in test");
+                flush(writer).expect("failed to flush writer in test");
+            }
+        }
+        let names: Vec<String> = DnaReader::from_path("test/data/append_written.fastq").map(|rec| rec.name).collect();
+        assert!(names == vec!["pewpew", "pewpew2", "pewpew", "pewpew2"]);
+        assert!(DnaReader::from_path("test/data/append_written.fastq.gz").count() == 4);
+    }
+
+    #[test]
+    fn test_compression_level() {
+        let mut sizes = Vec::new();
+        for level in [1, 9].iter() {
+            let filename = format!("test/data/many_written_level{}.fasta.gz", level);
+            let mut writer = DnaWriter::from_path_with_level(&filename, *level);
+            for rec in DnaReader::from_path("test/data/many.fasta") {
+                writer.write(&rec).expect("failed to write leveled gz in test");
+            }
+            flush(writer).expect("failed to flush writer in test");
+            sizes.push(std::fs::metadata(&filename).expect("written test data not available").len());
+            assert!(DnaReader::from_path(&filename).count() == 200);
+        }
+        assert!(sizes[1] < sizes[0]);
+    }
+
+    #[test]
+    fn test_bam_compression_level() {
+        // the same 40 bases over and over so there is something for the higher level to find
+        let recs: Vec<DnaRecord> = (0..500).map(|i| DnaRecord{ name: format!("read{}", i), seq: "ACGTTGCAAGGCTTAACCGGATATCGCGTTAAGCTAGCTA".repeat(4),
+            qual: Some("IIIIIIIIIIFFFFFFFFFF".repeat(8)), ..Default::default() }).collect();
+        let mut sizes = Vec::new();
+        for level in [1, 9].iter() {
+            let filename = format!("test/data/repeats_written_level{}.bam", level);
+            let mut writer = DnaWriterBuilder::new().level(*level).build(&filename).expect("failed to make bam writer in test");
+            writer.write_all(recs.clone()).expect("failed to write bam in test");
+            writer.close().expect("failed to close writer in test");
+            sizes.push(std::fs::metadata(&filename).expect("written test data not available").len());
+            let mut decompressed = Vec::new();
+            flate2::read::MultiGzDecoder::new(File::open(&filename).expect("written test data not available")).read_to_end(&mut decompressed).expect("bam is not valid bgzf");
+            assert!(decompressed.starts_with(b"BAM\x01"));
+            let written: Vec<DnaRecord> = DnaReader::from_path(&filename).collect();
+            assert!(written.len() == recs.len());
+            for (rec1, rec2) in recs.iter().zip(written.iter()) {
+                assert!(rec1.name == rec2.name && rec1.seq == rec2.seq && rec1.qual == rec2.qual);
+            }
+        }
+        assert!(sizes[1] < sizes[0]);
+        // quals that dont match the bases are an error rather than a broken bam
+        let mut writer = DnaWriterBuilder::new().level(1).build("test/data/short_qual_written.bam").expect("failed to make bam writer in test");
+        let bad = DnaRecord{ name: "bad".to_string(), seq: "ACGT".to_string(), qual: Some("II".to_string()), ..Default::default() };
+        assert!(writer.write(&bad).is_err());
+    }
+
+    #[test]
+    fn test_bgzf_fastq() {
+        let reader = DnaReader::from_path("test/data/fastq.fastq");
+        let mut writer = DnaWriter::from_path_with_compression("test/data/fastq_written_bgzf.fastq.gz", Bgzf);
+        for rec in reader {
+            writer.write(&rec).expect("failed to write bgzf fastq file in test");
+        }
+        flush(writer).expect("failed to flush writer in test");
+        let mut bytes = Vec::new();
+        File::open("test/data/fastq_written_bgzf.fastq.gz").expect("written test data not available").read_to_end(&mut bytes).expect("cant read written test data");
+        assert!(bytes.ends_with(&BGZF_EOF));
+        assert!(bytes[12] == b'B' && bytes[13] == b'C');
+        let block_size = (bytes[16] as usize | (bytes[17] as usize) << 8) + 1;
+        assert!(block_size + BGZF_EOF.len() == bytes.len());
+        let reader = DnaReader::from_path("test/data/fastq_written_bgzf.fastq.gz");
+        let original = DnaReader::from_path("test/data/fastq.fastq");
+        let mut count = 0;
+        for (rec1, rec2) in reader.zip(original) {
+            assert!(rec1.seq == rec2.seq);
+            count += 1;
+        }
+        assert!(count == 2);
+    }
+
+    #[test]
+    fn test_paired_fastq() {
+        let reader = PairedFastqReader::new("test/data/paired_R1.fastq", "test/data/paired_R2.fastq");
+        let pairs: Vec<(DnaRecord, DnaRecord)> = reader.collect();
+        assert!(pairs.len() == 3);
+        assert!(pairs[0].0.name == "pair1/1");
+        assert!(pairs[0].1.name == "pair1/2");
+        assert!(pairs[2].0.seq == "GGGGCCCC");
+        assert!(pairs[2].1.seq == "GGGGTTTT");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_paired_fastq_mismatch() {
+        let reader = PairedFastqReader::new("test/data/paired_R1.fastq", "test/data/fastq.fastq");
+        for _ in reader {}
+    }
+
+    #[test]
+    fn test_detect_interleaved() {
+        let mut reader = DnaReader::from_path("test/data/interleaved.fastq");
+        assert!(reader.detect_interleaved());
+        let names: Vec<String> = reader.map(|rec| rec.name).collect();
+        assert!(names == vec!["pair1/1", "pair1/2", "pair2/1", "pair2/2", "pair3/1", "pair3/2"]);
+        assert!(DnaReader::from_path("test/data/interleaved_casava.fastq").detect_interleaved());
+        let mut reader = DnaReader::from_path("test/data/paired_R1.fastq");
+        assert!(!reader.detect_interleaved());
+        assert!(reader.count() == 3);
+        assert!(!DnaReader::from_path("test/data/fastq.fastq").detect_interleaved());
+        let mut reader = DnaReader::from_path("test/data/interleaved.fastq");
+        reader.detect_interleaved();
+        assert!(reader.count_records() == 6);
+    }
+
+    #[test]
+    fn test_interleaved_fastq() {
+        let mut writer = InterleavedFastqWriter::new("test/data/interleaved_written.fastq", Uncompressed);
+        let a1 = DnaRecord{ name: "a".to_string(), seq: "ACGT".to_string(), qual: Some("IIII".to_string()), ..Default::default() };
+        let a2 = DnaRecord{ name: "a".to_string(), seq: "TTTT".to_string(), qual: Some("HHHH".to_string()), ..Default::default() };
+        let b1 = DnaRecord{ name: "b/1 extra".to_string(), seq: "GGGG".to_string(), qual: Some("IIII".to_string()), ..Default::default() };
+        let b2 = DnaRecord{ name: "b/2 extra".to_string(), seq: "CCCC".to_string(), qual: Some("HHHH".to_string()), ..Default::default() };
+        writer.write_pair(&a1, &a2).expect("failed to write first pair");
+        writer.write_pair(&b1, &b2).expect("failed to write second pair");
+        drop(writer);
+        let mut contents = String::new();
+        File::open("test/data/interleaved_written.fastq").expect("written test data not available").read_to_string(&mut contents).expect("cant read written test data");
+        let lines: Vec<&str> = contents.lines().collect();
+        assert!(lines.len() == 16);
+        assert!(lines[0] == "@a/1" && lines[1] == "ACGT");
+        assert!(lines[4] == "@a/2" && lines[5] == "TTTT");
+        assert!(lines[8] == "@b/1 extra" && lines[9] == "GGGG");
+        assert!(lines[12] == "@b/2 extra" && lines[13] == "CCCC");
+    }
+
+    #[test]
+    fn test_writer_try_from_path() {
+        let _ = std::fs::remove_dir_all("test/data/nested_written");
+        match DnaWriter::try_from_path("test/data/nested_written/sub/reads.fastq.gz", false) {
+            Err(DnaError::Io(_)) => (),
+            _ => panic!("missing directory should be an io error without create_dirs"),
+        }
+        let mut writer = DnaWriter::try_from_path("test/data/nested_written/sub/reads.fastq.gz", true).expect("failed to create nested writer in test");
+        writer.write_all(DnaReader::from_path("test/data/fastq.fastq")).expect("failed to write nested fastq in test");
+        flush(writer).expect("failed to flush writer in test");
+        let original: Vec<DnaRecord> = DnaReader::from_path("test/data/fastq.fastq").collect();
+        let written: Vec<DnaRecord> = DnaReader::from_path("test/data/nested_written/sub/reads.fastq.gz").collect();
+        assert!(original == written);
+        match DnaWriter::try_from_path("test/data/nested_written/reads.bam", true) {
+            Err(DnaError::Unsupported(_)) => (),
+            _ => panic!("bam without a header should be unsupported"),
+        }
+        match DnaWriter::try_from_path("test/data/nested_written/reads", true) {
+            Err(DnaError::UnknownExtension(_)) => (),
+            _ => panic!("no extension should be an unknown extension"),
+        }
+    }
+
+    #[test]
+    fn test_tee_writer() {
+        let tee = TeeWriter{ a: DnaWriter::from_path("test/data/tee_written.fastq").writer, b: DnaWriter::from_path("test/data/tee_written.fasta").writer };
+        let mut writer = DnaWriter{ writer: Box::new(tee) };
+        writer.write_all(DnaReader::from_path("test/data/fastq.fastq")).expect("failed to tee in test");
+        assert!(writer.records_written() == 2);
+        flush(writer).expect("failed to flush writer in test");
+        let original: Vec<DnaRecord> = DnaReader::from_path("test/data/fastq.fastq").collect();
+        let fastq: Vec<DnaRecord> = DnaReader::from_path("test/data/tee_written.fastq").collect();
+        let fasta: Vec<DnaRecord> = DnaReader::from_path("test/data/tee_written.fasta").collect();
+        assert!(original.len() == fastq.len() && original.len() == fasta.len());
+        for ((rec, fq), fa) in original.iter().zip(fastq.iter()).zip(fasta.iter()) {
+            assert!(rec.name == fq.name && rec.seq == fq.seq && rec.qual == fq.qual);
+            assert!(rec.name == fa.name && rec.seq == fa.seq && fa.qual.is_none());
+        }
+    }
+
+    #[test]
+    fn test_reservoir_sample() {
+        let first: Vec<String> = DnaReader::from_path("test/data/twenty.fasta").reservoir_sample(5, 42).into_iter().map(|rec| rec.name).collect();
+        let again: Vec<String> = DnaReader::from_path("test/data/twenty.fasta").reservoir_sample(5, 42).into_iter().map(|rec| rec.name).collect();
+        let other: Vec<String> = DnaReader::from_path("test/data/twenty.fasta").reservoir_sample(5, 7).into_iter().map(|rec| rec.name).collect();
+        assert!(first.len() == 5 && first == again && first != other);
+        let mut distinct = first.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert!(distinct.len() == 5);
+        // asking for more than there are just gives back everything
+        assert!(DnaReader::from_path("test/data/twenty.fasta").reservoir_sample(50, 42).len() == 20);
+        assert!(DnaReader::from_path("test/data/twenty.fasta").reservoir_sample(0, 42).is_empty());
+    }
+
+    #[test]
+    fn test_subsample() {
+        let first: Vec<String> = DnaReader::from_path("test/data/many.fasta").subsample(0.3, 42).map(|rec| rec.name).collect();
+        let again: Vec<String> = DnaReader::from_path("test/data/many.fasta").subsample(0.3, 42).map(|rec| rec.name).collect();
+        let other: Vec<String> = DnaReader::from_path("test/data/many.fasta").subsample(0.3, 7).map(|rec| rec.name).collect();
+        assert!(first == again);
+        assert!(first != other);
+        assert!(first.len() > 30 && first.len() < 90);
+        assert!(DnaReader::from_path("test/data/many.fasta").subsample(1.0, 42).count() == 200);
+        assert!(DnaReader::from_path("test/data/many.fasta").subsample(0.0, 42).count() == 0);
+        let r1: Vec<DnaRecord> = DnaReader::from_path("test/data/paired_R1.fastq").subsample(0.5, 3).collect();
+        let r2: Vec<DnaRecord> = DnaReader::from_path("test/data/paired_R2.fastq").subsample(0.5, 3).collect();
+        assert!(r1.len() == r2.len());
+        for (rec1, rec2) in r1.iter().zip(r2.iter()) {
+            assert!(pair_stem(&rec1.name) == pair_stem(&rec2.name));
+        }
+    }
+
+    #[test]
+    fn test_min_length() {
+        let names: Vec<String> = DnaReader::from_path("test/data/mixed_length.fasta").min_length(5).map(|rec| rec.name).collect();
+        assert!(names == vec!["five".to_string(), "ten".to_string()]);
+        assert!(DnaReader::from_path("test/data/mixed_length.fasta").min_length(0).count() == 4);
+        assert!(DnaReader::from_path("test/data/mixed_length.fasta").min_length(11).count() == 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "bad1")]
+    fn test_validate_alphabet() {
+        let data = b"@good1\nACGTNacgtn\n+\nIIIIIIIIII\n@bad1\nACZT\n+\nIIII\n".to_vec();
+        let mut reader = DnaReader::from_read(Box::new(Cursor::new(data)), Fastq, Uncompressed).with_validation();
+        assert!(reader.next().is_some());
+        reader.next();
+    }
+
+    #[test]
+    #[should_panic(expected = "bad1")]
+    fn test_validate_alphabet_fastq_reader() {
+        let data = b"@bad1\nACZT\n+\nIIII\n".to_vec();
+        let mut reader = FastqReader::from_read(Box::new(Cursor::new(data)), Uncompressed);
+        reader.validate_alphabet = true;
+        reader.next();
+    }
+
+    #[test]
+    fn test_validate_alphabet_off() {
+        let data = b"@bad1\nACZT\n+\nIIII\n".to_vec();
+        let reader = DnaReader::from_read(Box::new(Cursor::new(data)), Fastq, Uncompressed);
+        assert!(reader.count() == 1);
+        assert!(DnaReader::from_path("test/data/many.fasta").with_validation().count() == 200);
+    }
+
+    #[test]
+    fn test_records_result() {
+        let mut reader = DnaReader::from_path("test/data/truncated.fastq");
+        let results: Vec<Result<DnaRecord, DnaError>> = reader.records().collect();
+        assert!(results.len() == 2);
+        match results[0] {
+            Ok(ref rec) => assert!(rec.name == "r1" && rec.seq == "ACGT"),
+            Err(ref err) => panic!("first record should be fine: {}", err),
+        }
+        match results[1] {
+            Err(DnaError::Parse(ref msg)) => assert!(msg.contains("r2")),
+            _ => panic!("expected a parse error for the truncated record"),
+        }
+        let mut reader = DnaReader::from_path("test/data/fastq.fastq");
+        reader.peek();
+        assert!(reader.records().filter_map(|rec| rec.ok()).count() == 2);
+    }
+
+    #[test]
+    fn test_fastq_strict_separator() {
+        let mut reader = FastqReader::new("test/data/plus_named.fastq", Uncompressed).expect("test data not available");
+        reader.strict = true;
+        let recs: Vec<DnaRecord> = DnaReader::new(Box::new(reader)).collect();
+        assert!(recs.len() == 2 && recs[0].name == "read1 extra");
+        let mut reader = FastqReader::new("test/data/plus_mismatch.fastq", Uncompressed).expect("test data not available");
+        reader.strict = true;
+        let mut reader = DnaReader::new(Box::new(reader));
+        let results: Vec<Result<DnaRecord, DnaError>> = reader.records().collect();
+        assert!(results[0].is_ok());
+        match results[1] {
+            Err(DnaError::Parse(ref msg)) => assert!(msg.contains("read2") && msg.contains("+read1")),
+            _ => panic!("+read1 under read2 should be an error"),
+        }
+        // ignored when not strict
+        assert!(DnaReader::from_path("test/data/plus_mismatch.fastq").count() == 2);
+    }
+
+    #[test]
+    fn test_wrapped_fastq() {
+        // quality lines starting with @ on purpose
+        let recs: Vec<DnaRecord> = DnaReader::from_path("test/data/wrapped.fastq").collect();
+        assert!(recs.len() == 3);
+        assert!(recs[0].seq == "ACGTACGTACGGTT" && recs[0].qual == Some("IIIIIIIIII@@@@".to_string()));
+        assert!(recs[1].name == "short" && recs[1].seq == "AC" && recs[1].qual == Some("@I".to_string()));
+        assert!(recs[2].name == "wrapped3" && recs[2].seq == "AAAACCGG" && recs[2].qual == Some("IIIIIIII".to_string()));
+        assert!(DnaReader::from_path("test/data/wrapped.fastq").count_records() == 3);
+        let mut reader = DnaReader::from_path("test/data/wrapped.fastq");
+        reader.next();
+        assert!(reader.count_records() == 2);
+        // unwrapped files still read the same
+        let recs: Vec<DnaRecord> = DnaReader::from_path("test/data/fastq.fastq").collect();
+        assert!(recs.len() == 2 && recs.iter().all(|rec| rec.qual.as_ref().map(|qual| qual.len()) == Some(rec.seq.len())));
+    }
+
+    #[test]
+    #[should_panic(expected = "truncated")]
+    fn test_truncated_fastq_panics() {
+        DnaReader::from_path("test/data/truncated.fastq").count();
+    }
+
+    #[test]
+    #[should_panic(expected = "truncated")]
+    fn test_truncated_fastq_count_panics() {
+        DnaReader::from_path("test/data/truncated.fastq").count_records();
+    }
+
+    #[test]
+    fn test_fastq_trailing_blank_lines() {
+        let recs: Vec<DnaRecord> = DnaReader::from_path("test/data/trailing_blank.fastq").collect();
+        assert!(recs.len() == 2 && recs[1].name == "r2" && recs[1].qual == Some("HHHH".to_string()));
+        assert!(DnaReader::from_path("test/data/trailing_blank.fastq").count_records() == 2);
+    }
+
+    #[test]
+    fn test_peek() {
+        let mut reader = DnaReader::from_path("test/data/fastq.fastq");
+        assert!(reader.peek().expect("nothing to peek").name == "pewpew");
+        assert!(reader.peek().expect("nothing to peek twice").name == "pewpew");
+        let names: Vec<String> = reader.map(|rec| rec.name).collect();
+        assert!(names == vec!["pewpew".to_string(), "pewpew2".to_string()]);
+
+        let mut reader = DnaReader::from_path("test/data/fastq.fastq");
+        reader.next();
+        reader.next();
+        assert!(reader.peek().is_none());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_quality_encoding() {
+        let rec = DnaRecord{ name: "old".to_string(), seq: "ACGTAC".to_string(), qual: Some("hhhhBB".to_string()), ..Default::default() };
+        assert!(rec.phred_scores(64) == Some(vec![40, 40, 40, 40, 2, 2]));
+        let rescaled = rec.rescale_quality(64, 33);
+        assert!(rescaled.qual == Some("IIII##".to_string()));
+        assert!(rescaled.seq == rec.seq);
+        let rec = DnaRecord{ name: "fasta".to_string(), seq: "ACGT".to_string(), qual: None, ..Default::default() };
+        assert!(rec.phred_scores(33).is_none());
+        assert!(rec.rescale_quality(64, 33).qual.is_none());
+
+        let mut reader = FastqReader::new("test/data/phred64.fastq", Uncompressed).expect("test data not available");
+        assert!(reader.guess_encoding() == 64);
+        assert!(reader.next().expect("guess_encoding ate the first record").name == "old1");
+        assert!(reader.next().expect("guess_encoding ate the second record").name == "old2");
+        assert!(reader.next().is_none());
+        let mut reader = FastqReader::new("test/data/fastq.fastq", Uncompressed).expect("test data not available");
+        assert!(reader.guess_encoding() == 33);
+    }
+
     #[test]
     fn test_fasta() {
         let mut reader = DnaReader::from_path("test/data/fasta.fasta");
@@ -512,6 +4576,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_many_fasta() {
+        let reader = DnaReader::from_path("test/data/many.fasta");
+        let mut count = 0;
+        for rec in reader {
+            assert!(rec.name == format!("seq{}", count));
+            assert!(rec.seq.len() == 150);
+            count += 1;
+        }
+        assert!(count == 200);
+    }
+
     #[test]
     fn test_bam() {
         let mut reader = DnaReader::from_path("test/data/test.bam");
@@ -521,6 +4597,255 @@ mod tests {
         };
         println!("{}",rec.seq);
         assert!("GTCCTAAAACCCTAACCCTAACCCTAACCCTAACCCTAACCCTAACCCTAAACCTAACCCTAACCATACCCATAACCCCAACCCTAACACTAACCCCAAACCCAACCATAACCAACACCCCACACCTA" == rec.seq);
+        // samtools view: flag 99, chr1, 1-based pos 9995, mapq 37
+        assert!(rec.flags == Some(99));
+        assert!(rec.tid == Some(0));
+        assert!(rec.pos == Some(9994));
+        assert!(rec.mapq == Some(37));
+
+        let mut reader = DnaReader::from_path("test/data/fastq.fastq");
+        let rec = reader.next().expect("no fastq records");
+        assert!(rec.flags.is_none() && rec.tid.is_none() && rec.pos.is_none() && rec.mapq.is_none());
+    }
+
+    #[test]
+    fn test_sam() {
+        let mut reader = DnaReader::from_path("test/data/test.sam");
+        let rec = match reader.next() {
+            Some(x) => x,
+            None => panic!("sam reader doesnt work"),
+        };
+        assert!("ST-E00273:259:H7WY3ALXX:1:2203:31741:48388" == rec.name);
+        assert!("GTCCTAAAACCCTAACCCTAACCCTAACCCTAACCCTAACCCTAACCCTAAACCTAACCCTAACCATACCCATAACCCCAACCCTAACACTAACCCCAAACCCAACCATAACCAACACCCCACACCTA" == rec.seq);
+        let rec = match reader.next() {
+            Some(x) => x,
+            None => panic!("no second sam record"),
+        };
+        assert!("ST-E00273:259:H7WY3ALXX:2:2216:32207:1379" == rec.name);
+        assert!("GTCCTAACCCTAACCCGAACCCTAACCCTAACCCTAACCCTAACCCTAACCCTAACCCTAACCCTAACCCTAACCCTAACCCTAACCCTAACCCTAAACCCAACCCTAACCCCAACCCTAACCCCAAC" == rec.seq);
+        assert!(rec.qual.is_some());
+        assert!(2 + reader.count() == 26);
+    }
+
+    #[test]
+    fn test_sam_header_round_trip() {
+        let reader = DnaReader::from_path("test/data/header.sam");
+        let header = String::from_utf8(reader.header().expect("sam should have a header").to_bytes()).expect("header not utf8");
+        assert!(header.contains("@HD\tVN:1.6\tSO:unsorted") && header.contains("@SQ\tSN:chr1\tLN:1000"));
+        assert!(header.contains("@CO\tsome free text: with a colon"));
+        assert!(DnaReader::from_path("test/data/fastq.fastq").header().is_none());
+        convert("test/data/header.sam", "test/data/header_written.sam").expect("failed to convert sam in test");
+        let mut contents = String::new();
+        File::open("test/data/header_written.sam").expect("written test data not available").read_to_string(&mut contents).expect("cant read written test data");
+        let lines: Vec<&str> = contents.lines().collect();
+        assert!(lines[0] == "@HD\tVN:1.6\tSO:unsorted" && lines[1] == "@SQ\tSN:chr1\tLN:1000" && lines[2] == "@CO\tsome free text: with a colon");
+        let written: Vec<DnaRecord> = DnaReader::from_path("test/data/header_written.sam").collect();
+        assert!(written.len() == 2 && written[1].seq == "GGGCCCAA");
+    }
+
+    #[test]
+    fn test_bam_qual_to_string() {
+        assert!(bam_qual_to_string(&[0, 40, 93]) == Some("!I~".to_string()));
+        assert!(bam_qual_to_string(&[40, 94, 230, 255]) == Some("I~~~".to_string()));
+        assert!(bam_qual_to_string(&[0xFF, 0xFF]).is_none() && bam_qual_to_string(&[]).is_none());
+    }
+
+    #[test]
+    fn test_bam_long_name() {
+        let rec = DnaRecord{ name: "r".repeat(255), seq: "ACGT".to_string(), qual: Some("IIII".to_string()), ..Default::default() };
+        match unmapped_bam_record(&rec) {
+            Err(err) => assert!(err.kind() == ErrorKind::InvalidInput && err.to_string().contains("255 byte name")),
+            Ok(_) => panic!("a 255 byte name doesnt fit in bam"),
+        }
+    }
+
+    #[test]
+    fn test_bam_fetch() {
+        let mut reader = DnaReader::from_path("test/data/test.bam");
+        reader.fetch("chr1:9995-9995").expect("fetch failed on indexed bam");
+        let recs: Vec<DnaRecord> = reader.by_ref().collect();
+        assert!(recs.len() == 3);
+        for rec in recs.iter() {
+            assert!(rec.pos == Some(9994));
+        }
+        reader.fetch("chr2").expect("fetch failed on indexed bam");
+        assert!(reader.next().is_none());
+        match reader.fetch("chrNope:1-10") {
+            Err(DnaError::Parse(_)) => (),
+            _ => panic!("expected an error for an unknown contig"),
+        }
+
+        let mut reader = DnaReader::from_path("test/data/fastq.fastq");
+        match reader.fetch("chr1:1-10") {
+            Err(DnaError::Unsupported(_)) => (),
+            _ => panic!("fetch should not work on fastq"),
+        }
+    }
+
+    #[test]
+    fn test_parse_region() {
+        assert!(parse_region("chr1").ok() == Some(("chr1".to_string(), 0, None)));
+        assert!(parse_region("chr1:1,000-2,000").ok() == Some(("chr1".to_string(), 999, Some(2000))));
+        assert!(parse_region("chr1:1000").ok() == Some(("chr1".to_string(), 999, None)));
+        assert!(parse_region("chr1:abc").is_err());
+        assert!(parse_region("chr1:20-10").is_err());
+    }
+
+    #[test]
+    fn test_bam_flag_filters() {
+        let all: Vec<DnaRecord> = DnaReader::from_path("test/data/secondary.bam").collect();
+        assert!(all.len() == 5);
+        let bam = BamReader::new("test/data/secondary.bam").expect("test data not available")
+            .skip_secondary(true).skip_supplementary(true);
+        let primary: Vec<DnaRecord> = DnaReader::new(Box::new(bam)).collect();
+        assert!(primary.len() == 3);
+        assert!(primary.iter().all(|rec| rec.flags.unwrap() & 0x900 == 0));
+        assert!(primary[0].name == "primary" && primary[1].name == "chimera" && primary[2].name == "lost");
+        let bam = BamReader::new("test/data/secondary.bam").expect("test data not available")
+            .skip_secondary(true).skip_supplementary(true).skip_unmapped(true);
+        assert!(DnaReader::new(Box::new(bam)).count_records() == 2);
+        let bam = BamReader::new("test/data/secondary.bam").expect("test data not available")
+            .skip_unmapped(true).skip_unmapped(false);
+        assert!(DnaReader::new(Box::new(bam)).count() == 5);
+    }
+
+    #[test]
+    fn test_bam_pairs() {
+        let mut bam = BamReader::new("test/data/name_sorted.bam").expect("test data not available");
+        let pairs: Vec<(DnaRecord, DnaRecord)> = bam.pairs().expect("bam is name sorted").collect();
+        assert!(pairs.len() == 2);
+        assert!(pairs[0].0.name == "pairA" && pairs[0].0.pos == Some(100) && pairs[0].1.pos == Some(300));
+        // read2 comes first in the file and the secondary is skipped
+        assert!(pairs[1].0.flags.unwrap() & 0x40 != 0 && pairs[1].0.pos == Some(400) && pairs[1].1.pos == Some(500));
+        let mut unsorted = BamReader::new("test/data/coordinate_sorted.bam").expect("test data not available");
+        assert!(unsorted.pairs().is_err());
+    }
+
+    #[test]
+    fn test_bam_original_orientation() {
+        let bam = BamReader::new("test/data/reverse.bam").expect("test data not available").original_orientation(true);
+        let records: Vec<DnaRecord> = DnaReader::new(Box::new(bam)).collect();
+        assert!(records.len() == 2);
+        for rec in records.iter() {
+            assert!(rec.seq == "ACCGTTTA");
+            assert!(rec.qual == Some("ABCDEFGH".to_string()));
+        }
+        assert!(records[1].flags.unwrap() & 0x10 != 0);
+        // left as stored otherwise
+        let stored: Vec<DnaRecord> = DnaReader::from_path("test/data/reverse.bam").collect();
+        assert!(stored[1].seq == "TAAACGGT" && stored[1].qual == Some("HGFEDCBA".to_string()));
+        convert_lossy("test/data/reverse.bam", "test/data/reverse_written.fastq").expect("failed to convert in test");
+        let written: Vec<DnaRecord> = DnaReader::from_path("test/data/reverse_written.fastq").collect();
+        assert!(written[1].seq == "ACCGTTTA");
+    }
+
+    #[test]
+    fn test_bam_tags() {
+        let bam = BamReader::new("test/data/tagged.bam").expect("test data not available").with_tags(&["CB", "UB", "NH"]);
+        let records: Vec<DnaRecord> = DnaReader::new(Box::new(bam)).collect();
+        assert!(records.len() == 2);
+        let tags = records[0].tags.as_ref().expect("no tags read");
+        assert!(tags["CB"] == "AAACCCAAGAAACACT-1");
+        assert!(tags["UB"] == "ACGTACGTAC");
+        assert!(tags["NH"] == "1");
+        let tags = records[1].tags.as_ref().expect("no tags read");
+        assert!(tags["CB"] == "TTTGTTGTCTTACCGG-1");
+        assert!(!tags.contains_key("UB"));
+        // nothing asked for, nothing pulled
+        let records: Vec<DnaRecord> = DnaReader::from_path("test/data/tagged.bam").collect();
+        assert!(records[0].tags.is_none());
+    }
+
+    #[test]
+    fn test_write_cram() {
+        let reader = DnaReader::from_path("test/data/fastq.fastq");
+        let mut writer = DnaWriter{ writer: Box::new(CramWriter::new("test/data/fastq_written.cram", &reader)) };
+        writer.write_all(reader).expect("failed to write cram in test");
+        flush(writer).expect("failed to flush writer in test");
+        let original = DnaReader::from_path("test/data/fastq.fastq");
+        let written = DnaReader::from_path("test/data/fastq_written.cram");
+        let mut count = 0;
+        for (rec1, rec2) in original.zip(written) {
+            assert!(rec1.name == rec2.name);
+            assert!(rec1.seq == rec2.seq);
+            assert!(rec1.qual == rec2.qual);
+            count += 1;
+        }
+        assert!(count == DnaReader::from_path("test/data/fastq.fastq").count());
+    }
+
+    #[test]
+    fn test_write_bam() {
+        let reader = DnaReader::from_path("test/data/fastq.fastq");
+        let mut writer = DnaWriter{ writer: Box::new(BamWriter::new("test/data/fastq_written.bam", &reader, None)) };
+        for rec in reader {
+            writer.write(&rec).expect("failed to write bam file in test");
+        }
+        writer.write(&DnaRecord{ name: "noqual".to_string(), seq: "ACGT".to_string(), qual: None, ..Default::default() }).expect("failed to write record with no qual");
+        flush(writer).expect("failed to flush writer in test");
+        let written: Vec<DnaRecord> = DnaReader::from_path("test/data/fastq_written.bam").collect();
+        assert!(written.len() == 3);
+        assert!(written[0].name == "pewpew");
+        assert!(written[0].seq == "ACTGGTCA");
+        assert!(written[0].qual == Some("++++++++".to_string()));
+        assert!(written[2].seq == "ACGT");
+        assert!(written[2].qual.is_none());
+    }
+
+    #[test]
+    fn test_concatenated_gzip() {
+        // fastq.fastq and paired_R1.fastq gzipped on their own then cat'ed together
+        let names: Vec<String> = DnaReader::from_path("test/data/concat.fastq.gz").map(|rec| rec.name).collect();
+        assert!(names == vec!["pewpew", "pewpew2", "pair1/1", "pair2/1", "pair3/1"]);
+        let mut reader = DnaReader::from_path("test/data/concat.fastq.gz");
+        assert!(reader.count_records() == 5);
+    }
+
+    #[test]
+    fn test_sam_gz() {
+        let reader = DnaReader::from_path("test/data/test.sam.gz");
+        assert!(reader.extension() == ".sam.gz");
+        let gzipped: Vec<DnaRecord> = reader.collect();
+        let plain: Vec<DnaRecord> = DnaReader::from_path("test/data/test.sam").collect();
+        assert!(gzipped.len() == 26 && plain.len() == 26);
+        for (rec1, rec2) in plain.iter().zip(gzipped.iter()) {
+            assert!(rec1.name == rec2.name && rec1.seq == rec2.seq && rec1.qual == rec2.qual);
+        }
+    }
+
+    #[test]
+    fn test_from_header() {
+        let mut header = bam::Header::new();
+        let mut sq = bam::header::HeaderRecord::new(b"SQ");
+        sq.push_tag(b"SN", &"chr1").push_tag(b"LN", &248956422);
+        header.push_record(&sq);
+        let mut rg = bam::header::HeaderRecord::new(b"RG");
+        rg.push_tag(b"ID", &"lane1").push_tag(b"SM", &"sample1");
+        header.push_record(&rg);
+        let mut writer = DnaWriter::from_header("test/data/header_written.bam", header);
+        writer.write_all(DnaReader::from_path("test/data/fastq.fastq")).expect("failed to write bam with header in test");
+        flush(writer).expect("failed to flush writer in test");
+        let reader = DnaReader::from_path("test/data/header_written.bam");
+        let header = String::from_utf8(reader.header().expect("bam should have a header").to_bytes()).expect("header not utf8");
+        assert!(header.contains("@SQ\tSN:chr1\tLN:248956422"));
+        assert!(header.contains("@RG\tID:lane1\tSM:sample1"));
+        assert!(reader.count() == 2);
+    }
+
+    #[test]
+    fn test_write_sam() {
+        let reader = DnaReader::from_path("test/data/test.bam");
+        let mut writer = DnaWriter{ writer: Box::new(SamWriter::new("test/data/bam_written.sam", &reader)) };
+        for rec in reader {
+            writer.write(&rec).expect("failed to write sam file in test");
+        }
+        flush(writer).expect("failed to flush writer in test");
+        let mut original = DnaReader::from_path("test/data/test.bam");
+        let mut written = DnaReader::from_path("test/data/bam_written.sam");
+        let rec1 = original.next().expect("no bam records");
+        let rec2 = written.next().expect("no written sam records");
+        assert!(rec1.name == rec2.name);
+        assert!(rec1.seq == rec2.seq);
     }
 
     #[test]
@@ -530,7 +4855,7 @@ mod tests {
         for rec in reader {
             writer.write(&rec).expect("failed to write fastq file in test");
         }
-        flush(writer);
+        flush(writer).expect("failed to flush writer in test");
         let mut file = File::open("test/data/fastq.fastq").expect("test data not available");
         let mut contents = String::new();
         file.read_to_string(&mut contents).expect("cant read test data");
@@ -540,6 +4865,644 @@ mod tests {
         assert!(contents == contents2);
     }
     
+    #[test]
+    fn test_write_all() {
+        let reader = DnaReader::from_path("test/data/fastq.fastq");
+        let mut writer = DnaWriter::from_path("test/data/fastq_written_all.fastq");
+        writer.write_all(reader).expect("failed to write_all in test");
+        flush(writer).expect("failed to flush writer in test");
+        let mut original = Vec::new();
+        File::open("test/data/fastq.fastq").expect("test data not available").read_to_end(&mut original).expect("cant read test data");
+        let mut written = Vec::new();
+        File::open("test/data/fastq_written_all.fastq").expect("written test data not available").read_to_end(&mut written).expect("cant read written test data");
+        assert!(original == written);
+    }
+
+    #[test]
+    fn test_illumina_header() {
+        let rec = DnaRecord{ name: "A00123:45:HABCDEFXX:2:1101:12345:1000 1:N:0:ACGTACGT+TTGACCAA".to_string(), ..Default::default() };
+        let header = rec.illumina_header().expect("should parse as illumina");
+        assert!(header.instrument == "A00123" && header.run == 45 && header.flowcell == "HABCDEFXX");
+        assert!(header.lane == 2 && header.tile == 1101 && header.x == 12345 && header.y == 1000);
+        assert!(header.read == Some(1) && header.filtered == Some(false) && header.control == Some(0));
+        assert!(header.index == Some("ACGTACGT+TTGACCAA".to_string()));
+        // fasta style, comment in the description
+        let rec = DnaRecord{ name: "M01234:7:000000000-ABCDE:1:2104:9876:543".to_string(), description: Some("2:Y:0:7".to_string()), ..Default::default() };
+        let header = rec.illumina_header().expect("should parse as illumina");
+        assert!(header.lane == 1 && header.read == Some(2) && header.filtered == Some(true) && header.index == Some("7".to_string()));
+        // no comment at all, and a /1 on the end
+        let rec = DnaRecord{ name: "A00123:45:HABCDEFXX:2:1101:12345:1000/1".to_string(), ..Default::default() };
+        let header = rec.illumina_header().expect("should parse as illumina");
+        assert!(header.y == 1000 && header.read.is_none() && header.index.is_none());
+        for name in ["pewpew", "SRR123.1 1 length=150", "a:b:c:d:e:f:g", "A00123:45:HABCDEFXX:2:1101:12345"].iter() {
+            let rec = DnaRecord{ name: name.to_string(), ..Default::default() };
+            assert!(rec.illumina_header().is_none());
+        }
+    }
+
+    #[test]
+    fn test_reader_builder() {
+        let reader = DnaReader::builder().buffer_size(1024).validate(true).build("test/data/fastq.fastq").expect("failed to build reader in test");
+        assert!(reader.validate_alphabet);
+        let built: Vec<DnaRecord> = reader.collect();
+        let plain: Vec<DnaRecord> = DnaReader::from_path("test/data/fastq.fastq").collect();
+        assert!(built == plain);
+        let reader = DnaReaderBuilder::new().format(Fastq).compression(Uncompressed).buffer_size(64).build("test/data/fastq.fq").expect("failed to build reader in test");
+        assert!(reader.my_type() == Fastq && reader.count() == 2);
+        // compression alone still needs the extension for the format
+        let reader = DnaReaderBuilder::new().compression(Bgzf).build("test/data/fasta.fasta.gz").expect("failed to build reader in test");
+        assert!(reader.my_type() == Fasta && reader.count() > 0);
+        match DnaReaderBuilder::new().compression(Uncompressed).build("test/data/no_extension") {
+            Err(DnaError::UnknownExtension(_)) => (),
+            _ => panic!("no format and no extension should error"),
+        }
+    }
+
+    #[test]
+    fn test_bgz_extension() {
+        assert!(check_extension("test/data/fastq.fastq.bgz") == (Fastq, Bgzf));
+        assert!(check_extension("genome.fa.bgz") == (Fasta, Bgzf));
+        let bgz: Vec<DnaRecord> = DnaReader::from_path("test/data/fastq.fastq.bgz").collect();
+        let plain: Vec<DnaRecord> = DnaReader::from_path("test/data/fastq.fastq").collect();
+        assert!(bgz.len() == 2 && bgz == plain);
+        let mut reader = BgzfFastqReader::new("test/data/fastq.fastq.bgz").expect("test data not available");
+        assert!(reader.next().expect("no records").name == "pewpew");
+    }
+
+    #[test]
+    fn test_build_fai() {
+        // indexed.fasta.fai is what samtools faidx makes for indexed.fasta
+        std::fs::copy("test/data/indexed.fasta", "test/data/indexed_written.fasta").expect("test data not available");
+        build_fai("test/data/indexed_written.fasta").expect("failed to build fai in test");
+        let mut expected = String::new();
+        File::open("test/data/indexed.fasta.fai").expect("test data not available").read_to_string(&mut expected).expect("cant read test data");
+        let mut written = String::new();
+        File::open("test/data/indexed_written.fasta.fai").expect("written test data not available").read_to_string(&mut written).expect("cant read written test data");
+        assert!(written == expected);
+        assert!(written == "chr1\t28\t19\t10\t11\nchr2\t13\t56\t10\t11\n");
+        // nooo has 9, 7 then 4 bases a line
+        match build_fai("test/data/fasta.fasta") {
+            Err(DnaError::Parse(msg)) => assert!(msg.contains("nooo")),
+            _ => panic!("uneven lines should not index"),
+        }
+        match build_fai("test/data/fasta.fasta.gz") {
+            Err(DnaError::Unsupported(_)) => (),
+            _ => panic!("gzipped fasta should not index"),
+        }
+    }
+
+    #[test]
+    fn test_indexed_fasta() {
+        let mut reader = IndexedFastaReader::from_path("test/data/indexed.fasta", "test/data/indexed.fasta.fai").expect("test data not available");
+        let rec = reader.fetch("chr1", 5, 10).expect("failed to fetch in test");
+        assert!(rec.name == "chr1:5-10" && rec.seq == "ACGTAC");
+        // across line breaks
+        assert!(reader.fetch("chr1", 9, 22).expect("failed to fetch in test").seq == "ACGGGGCCCCTTAA");
+        assert!(reader.fetch("chr1", 1, 28).expect("failed to fetch in test").seq == "ACGTACGTACGGGGCCCCTTAATTAACC");
+        let rec = reader.fetch("chr2", 10, 100).expect("failed to fetch in test");
+        assert!(rec.seq == "NACG" && rec.name == "chr2:10-13");
+        assert!(reader.length("chr2") == Some(13));
+        assert!(reader.fetch("chr3", 1, 2).is_err());
+        assert!(reader.fetch("chr1", 0, 2).is_err());
+        assert!(reader.fetch("chr1", 29, 30).is_err());
+    }
+
+    #[test]
+    fn test_split_fasta() {
+        let paths = split_fasta("test/data/three.fasta", "test/data/split_written").expect("failed to split in test");
+        assert!(paths == vec!["test/data/split_written/chr1.fasta", "test/data/split_written/contig_2.fasta", "test/data/split_written/chrM.fasta"]);
+        let originals: Vec<DnaRecord> = DnaReader::from_path("test/data/three.fasta").collect();
+        for (path, original) in paths.iter().zip(originals.iter()) {
+            let recs: Vec<DnaRecord> = DnaReader::from_path(path).collect();
+            assert!(recs.len() == 1 && recs[0] == *original);
+        }
+        match split_fasta("test/data/duplicate_names.fastq", "test/data/split_written_dups") {
+            Err(DnaError::Parse(msg)) => assert!(msg.contains("read1")),
+            _ => panic!("duplicate ids should be an error"),
+        }
+    }
+
+    #[test]
+    fn test_extract_bed() {
+        let recs: Vec<DnaRecord> = extract_bed("test/data/indexed.fasta", "test/data/indexed.bed").expect("test data not available")
+            .map(|rec| rec.expect("failed to extract interval in test")).collect();
+        assert!(recs.len() == 2);
+        // across a line break
+        assert!(recs[0].name == "chr1:8-14" && recs[0].seq == "ACGGGG");
+        // ACGTNACG on the - strand
+        assert!(recs[1].name == "chr2:5-13" && recs[1].seq == "CGTNACGT");
+        // browser and # lines skipped, an empty interval, then a contig that isnt there and a bad start
+        let results: Vec<Result<DnaRecord, DnaError>> = extract_bed("test/data/indexed.fasta", "test/data/edge_cases.bed")
+            .expect("test data not available").collect();
+        assert!(results.len() == 4);
+        match results[0] {
+            Ok(ref rec) => assert!(rec.name == "chr1:3-3" && rec.seq.is_empty()),
+            Err(ref err) => panic!("empty interval should be an empty record: {}", err),
+        }
+        match results[1] {
+            Ok(ref rec) => assert!(rec.name == "chr2:0-4" && rec.seq == "NNNN"),
+            Err(ref err) => panic!("chr2:0-4 should extract: {}", err),
+        }
+        match results[2] {
+            Err(DnaError::Parse(ref msg)) => assert!(msg.contains("chr3")),
+            _ => panic!("chr3 is not in the fasta"),
+        }
+        match results[3] {
+            Err(DnaError::Parse(ref msg)) => assert!(msg.contains("ten")),
+            _ => panic!("a start of ten should be an error"),
+        }
+        match extract_bed("test/data/indexed.fasta", "test/data/missing.bed") {
+            Err(DnaError::Io(ref err)) => assert!(err.kind() == ErrorKind::NotFound),
+            _ => panic!("missing bed should be an error"),
+        }
+        match extract_bed("test/data/fasta.fasta", "test/data/indexed.bed") {
+            Err(DnaError::Io(_)) => (),
+            _ => panic!("fasta without a .fai should be an error"),
+        }
+    }
+
+    #[test]
+    fn test_fna() {
+        let reader = DnaReader::from_path("test/data/fasta.fna.gz");
+        assert!(reader.my_type() == Fasta);
+        assert!(reader.extension() == ".fasta.gz");
+        let fna: Vec<DnaRecord> = reader.collect();
+        let fasta: Vec<DnaRecord> = DnaReader::from_path("test/data/fasta.fasta.gz").collect();
+        assert!(!fna.is_empty() && fna == fasta);
+        for name in ["genes.ffn", "rna.frn", "genome.fna"].iter() {
+            assert!(check_extension(name) == (Fasta, Uncompressed));
+        }
+    }
+
+    struct FullDisk;
+
+    impl std::io::Write for FullDisk {
+        fn write(&mut self, _buf: &[u8]) -> Result<usize, std::io::Error> { Err(std::io::Error::other("no space left on device")) }
+        fn flush(&mut self) -> Result<(), std::io::Error> { Ok(()) }
+    }
+
+    #[test]
+    fn test_close() {
+        for compression in [Uncompressed, Gzipped].iter() {
+            let mut writer = DnaWriter{ writer: Box::new(FastqWriter::from_write(Box::new(FullDisk), compression.clone())) };
+            // small enough to sit in the buffers, so nothing fails until the end
+            writer.write_all(DnaReader::from_path("test/data/fastq.fastq")).expect("buffered writes should not fail");
+            let err = writer.close().expect_err("close should report the failed write");
+            assert!(err.to_string().contains("no space left"));
+        }
+        let mut writer = DnaWriter::from_path("test/data/close_written.fastq.gz");
+        writer.write_all(DnaReader::from_path("test/data/fastq.fastq")).expect("failed to write in test");
+        writer.close().expect("failed to close writer in test");
+        assert!(DnaReader::from_path("test/data/close_written.fastq.gz").count() == 2);
+    }
+
+    #[test]
+    fn test_from_paths() {
+        let files = ["test/data/fastq.fastq", "test/data/paired_R1.fastq", "test/data/paired_R2.fastq"];
+        let names: Vec<String> = DnaReader::from_paths(&files).expect("failed to open files in test").map(|rec| rec.name).collect();
+        assert!(names.len() == 8);
+        assert!(names[0] == "pewpew" && names[2] == "pair1/1" && names[7] == "pair3/2");
+        let mut reader = DnaReader::from_paths(&files).expect("failed to open files in test");
+        assert!(reader.my_type() == Fastq);
+        assert!(reader.count_records() == 8);
+        reader.reset().expect("failed to reset in test");
+        assert!(reader.count() == 8);
+        match DnaReader::from_paths(&["test/data/fastq.fastq", "test/data/fastq.fastq.gz"]) {
+            Err(DnaError::Unsupported(_)) => (),
+            _ => panic!("mixed compression should error"),
+        }
+        match DnaReader::from_paths(&["test/data/fastq.fastq", "test/data/fasta.fasta"]) {
+            Err(DnaError::Unsupported(_)) => (),
+            _ => panic!("mixed formats should error"),
+        }
+        assert!(DnaReader::from_paths(&[]).is_err());
+    }
+
+    #[test]
+    fn test_error_probabilities() {
+        // phred+33 so ! is 0, + is 10, 5 is 20, ? is 30, I is 40
+        let rec = DnaRecord{ name: "read1".to_string(), seq: "ACGTA".to_string(), qual: Some("!+5?I".to_string()), ..Default::default() };
+        let probs = rec.error_probabilities(33).expect("record has a qual");
+        let expected = [1.0, 0.1, 0.01, 0.001, 0.0001];
+        assert!(probs.len() == 5);
+        for (p, e) in probs.iter().zip(expected.iter()) {
+            assert!((p - e).abs() < 1e-12);
+        }
+        assert!((rec.average_quality(33).unwrap() - 20.0).abs() < 1e-12);
+        let phred64 = rec.rescale_quality(33, 64);
+        assert!(phred64.error_probabilities(64) == rec.error_probabilities(33));
+        let fasta = DnaRecord{ name: "read1".to_string(), seq: "ACGT".to_string(), ..Default::default() };
+        assert!(fasta.error_probabilities(33).is_none() && fasta.average_quality(33).is_none());
+        let empty = DnaRecord{ qual: Some(String::new()), ..Default::default() };
+        assert!(empty.average_quality(33).is_none());
+    }
+
+    #[test]
+    fn test_stats() {
+        // lengths 1, 5, 4 and 10
+        let mut reader = DnaReader::from_path("test/data/mixed_length.fasta");
+        let stats = reader.stats();
+        assert!(reader.next().is_none());
+        assert!(stats.num_records == 4 && stats.total_length == 20);
+        assert!(stats.min_length == 1 && stats.max_length == 10 && stats.n50 == 10);
+        assert!((stats.mean_length - 5.0).abs() < 1e-9);
+        assert!((stats.gc_percent - 45.0).abs() < 1e-9);
+        assert!(stats.to_string().lines().any(|line| line.starts_with("n50") && line.ends_with(" 10")));
+        // 200 reads of 150
+        let stats = DnaReader::from_path("test/data/many.fasta").stats();
+        assert!(stats.total_length == 30000 && stats.n50 == 150);
+    }
+
+    #[test]
+    fn test_filter_names() {
+        // read2/1 and read4 extra match by their stems
+        let names: HashSet<String> = ["read2", "read4", "not_there"].iter().map(|name| name.to_string()).collect();
+        let kept: Vec<String> = DnaReader::from_path("test/data/five_names.fastq").filter_names(names.clone(), true).map(|rec| rec.name).collect();
+        assert!(kept == vec!["read2/1", "read4 extra"]);
+        let dropped: Vec<String> = DnaReader::from_path("test/data/five_names.fastq").filter_names(names, false).map(|rec| rec.name).collect();
+        assert!(dropped == vec!["read1", "read3", "read5"]);
+        assert!(DnaReader::from_path("test/data/five_names.fastq").filter_names(HashSet::new(), false).count() == 5);
+    }
+
+    #[test]
+    fn test_relabel() {
+        let mut writer = DnaWriter::from_path("test/data/relabel_written.fasta");
+        writer.write_all(DnaReader::from_path("test/data/fastq.fastq").relabel(|name| format!("sampleA_{}", name))).expect("failed to write in test");
+        flush(writer).expect("failed to flush writer in test");
+        let names: Vec<String> = DnaReader::from_path("test/data/relabel_written.fasta").map(|rec| rec.name).collect();
+        assert!(names == vec!["sampleA_pewpew", "sampleA_pewpew2"]);
+        let mut count = 0;
+        let numbered: Vec<String> = DnaReader::from_path("test/data/fastq.fastq").relabel(|_| { count += 1; format!("read{}", count) }).map(|rec| rec.name).collect();
+        assert!(numbered == vec!["read1", "read2"]);
+    }
+
+    #[test]
+    fn test_kmers() {
+        let pack = |kmer: &str| kmer.bytes().fold(0u64, |packed, base| (packed << 2) | "ACGT".find(base as char).unwrap() as u64);
+        // ACG and CGT are each others reverse complement, GTT comes out as AAC. the N leaves just aaa
+        let kmers: Vec<u64> = DnaReader::from_path("test/data/kmer_ns.txt").kmers(3).collect();
+        assert!(kmers == vec![pack("ACG"), pack("ACG"), pack("AAC"), pack("AAA")]);
+        let mut distinct: Vec<u64> = kmers.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert!(distinct == vec![pack("AAA"), pack("AAC"), pack("ACG")]);
+        assert!(DnaReader::from_path("test/data/kmer_ns.txt").kmers(6).count() == 0);
+        // the most any read can give
+        let kmers: Vec<u64> = DnaReader::from_path("test/data/many.fasta").kmers(32).collect();
+        assert!(kmers.len() == 200 * (150 - 31));
+    }
+
+    #[test]
+    fn test_digest() {
+        let mut reader = DnaReader::from_path("test/data/fastq.fastq").with_digest();
+        assert!(reader.by_ref().count() == 2);
+        // md5sum of "ACTGGTCA\nTTTTTTTT\n"
+        assert!(reader.finalize_digest() == "406ac14075a7da3d03b9fd64538bc841");
+        let mut gzipped = DnaReader::from_path("test/data/fastq.fastq.gz").with_digest();
+        gzipped.by_ref().count();
+        assert!(gzipped.finalize_digest() == reader.finalize_digest());
+        let mut other = DnaReader::from_path("test/data/paired_R1.fastq").with_digest();
+        other.by_ref().count();
+        assert!(other.finalize_digest() != reader.finalize_digest());
+        // bigger than one 64 byte block
+        let mut many = DnaReader::from_path("test/data/many.fasta").with_digest();
+        many.by_ref().count();
+        assert!(many.finalize_digest() == "6cd4b03ccdb5cbda48834f8e380a04c5");
+        assert!(DnaReader::from_path("test/data/fastq.fastq").with_digest().finalize_digest() == "d41d8cd98f00b204e9800998ecf8427e");
+    }
+
+    #[test]
+    fn test_entropy() {
+        let poly_a = DnaRecord{ name: "polyA".to_string(), seq: "A".repeat(50), ..Default::default() };
+        assert!(poly_a.shannon_entropy().abs() < 1e-12);
+        let mixed = DnaRecord{ name: "mixed".to_string(), seq: "ACGTacgt".to_string(), ..Default::default() };
+        assert!((mixed.shannon_entropy() - 2.0).abs() < 1e-12);
+        let half = DnaRecord{ name: "half".to_string(), seq: "AAAATTTT".to_string(), ..Default::default() };
+        assert!((half.shannon_entropy() - 1.0).abs() < 1e-12);
+        assert!(DnaRecord::default().shannon_entropy() == 0.0);
+        // duplicate_names.fastq has ACGT, GGGG, ACGT, TTTT, CCCC
+        let kept: Vec<DnaRecord> = DnaReader::from_path("test/data/duplicate_names.fastq").min_entropy(1.0).collect();
+        assert!(kept.len() == 2 && kept.iter().all(|rec| rec.seq == "ACGT"));
+    }
+
+    #[test]
+    fn test_dedup_by_name() {
+        let records: Vec<DnaRecord> = DnaReader::from_path("test/data/duplicate_names.fastq").dedup_by_name().collect();
+        let names: Vec<&str> = records.iter().map(|rec| rec.name.as_str()).collect();
+        assert!(names == vec!["read1", "read2", "read3"]);
+        // the first one wins
+        assert!(records[1].seq == "GGGG");
+        assert!(DnaReader::from_path("test/data/fastq.fastq").dedup_by_name().count() == 2);
+    }
+
+    #[test]
+    fn test_head() {
+        let mut reader = DnaReader::from_path("test/data/paired_R1.fastq");
+        let records = reader.head(100);
+        assert!(records.len() == 3);
+        assert!(reader.next().is_none());
+        let mut reader = DnaReader::from_path("test/data/paired_R1.fastq");
+        let first = reader.head(2);
+        assert!(first.len() == 2 && first[0].name == "pair1/1");
+        assert!(reader.next().expect("third record missing").name == "pair3/1");
+        assert!(DnaReader::from_path("test/data/paired_R1.fastq").head(0).is_empty());
+    }
+
+    #[test]
+    fn test_member_per_record() {
+        let mut fastq = FastqWriter::new("test/data/members_written.fastq.gz", Gzipped);
+        fastq.member_per_record = true;
+        let mut writer = DnaWriter{ writer: Box::new(fastq) };
+        writer.write_all(DnaReader::from_path("test/data/paired_R1.fastq")).expect("failed to write fastq in test");
+        flush(writer).expect("failed to flush writer in test");
+        let mut bytes = Vec::new();
+        File::open("test/data/members_written.fastq.gz").expect("written test data not available").read_to_end(&mut bytes).expect("cant read written test data");
+        // bufread's decoder stops at the end of one member and leaves the rest
+        let mut rest: &[u8] = &bytes;
+        let mut members = Vec::new();
+        while !rest.is_empty() {
+            let mut member = String::new();
+            flate2::bufread::GzDecoder::new(&mut rest).read_to_string(&mut member).expect("bad gzip member");
+            members.push(member);
+        }
+        assert!(members.len() == 3);
+        assert!(members.iter().all(|member| member.lines().count() == 4 && member.starts_with("@pair")));
+        let recs: Vec<DnaRecord> = DnaReader::from_path("test/data/members_written.fastq.gz").collect();
+        assert!(recs.len() == 3 && recs[2].name == "pair3/1");
+    }
+
+    #[test]
+    fn test_default_quality() {
+        let mut fastq = FastqWriter::new("test/data/default_qual_written.fastq", Uncompressed);
+        fastq.default_quality = Some(40);
+        let mut writer = DnaWriter{ writer: Box::new(fastq) };
+        writer.write_all(DnaReader::from_path("test/data/fasta.fasta")).expect("failed to write fastq in test");
+        flush(writer).expect("failed to flush writer in test");
+        let original = DnaReader::from_path("test/data/fasta.fasta");
+        let written: Vec<DnaRecord> = DnaReader::from_path("test/data/default_qual_written.fastq").collect();
+        assert!(written.len() == DnaReader::from_path("test/data/fasta.fasta").count());
+        for (rec1, rec2) in original.zip(written.iter()) {
+            assert!(rec1.name == rec2.name && rec1.seq == rec2.seq);
+            assert!(rec2.phred_scores(33).expect("no qual written").iter().all(|&q| q == 40));
+            assert!(rec2.qual.as_ref().unwrap().len() == rec2.seq.len());
+        }
+    }
+
+    #[test]
+    fn test_record_strings() {
+        let mut rec = DnaRecord{ name: "read1".to_string(), seq: "ACGT".to_string(), qual: Some("II#I".to_string()), ..Default::default() };
+        assert!(rec.to_fasta_string() == ">read1\nACGT\n");
+        assert!(rec.to_fastq_string().expect("record has a qual") == "@read1\nACGT\n+\nII#I\n");
+        rec.description = Some("sample=1".to_string());
+        assert!(rec.to_fasta_string() == ">read1 sample=1\nACGT\n");
+        assert!(rec.to_fastq_string().expect("record has a qual") == "@read1 sample=1\nACGT\n+\nII#I\n");
+        // wrapped at 60 like the writer
+        let long = DnaRecord{ name: "chr1".to_string(), seq: "A".repeat(130), ..Default::default() };
+        let expected = format!(">chr1\n{}\n{}\n{}\n", "A".repeat(60), "A".repeat(60), "A".repeat(10));
+        assert!(long.to_fasta_string() == expected);
+        match long.to_fastq_string() {
+            Err(DnaError::Unsupported(msg)) => assert!(msg.contains("chr1")),
+            _ => panic!("no qual should not make a fastq"),
+        }
+    }
+
+    #[test]
+    fn test_base_stats() {
+        let rec = DnaRecord{ name: "qc".to_string(), seq: "ACAAAAAGnnNNcgTTa".to_string(), ..Default::default() };
+        let stats = rec.base_stats();
+        assert!(stats.length == 17);
+        assert!(stats.n_count == 4);
+        assert!(stats.lowercase_count == 5);
+        assert!(stats.gc_count == 4);
+        assert!(stats.max_homopolymer == 5);
+        // runs go across case changes but not through Ns
+        let rec = DnaRecord{ name: "qc".to_string(), seq: "TTtTNNNNNNGG".to_string(), ..Default::default() };
+        assert!(rec.base_stats().max_homopolymer == 4);
+        assert!(DnaRecord::default().base_stats() == BaseStats::default());
+        let total = DnaReader::from_path("test/data/fasta.fasta").map(|rec| rec.base_stats()).fold(BaseStats::default(), |acc, stats| acc.merge(&stats));
+        let length: usize = DnaReader::from_path("test/data/fasta.fasta").map(|rec| rec.len()).sum();
+        assert!(total.length == length);
+    }
+
+    #[test]
+    fn test_record_clone_eq() {
+        let rec = DnaRecord{ name: "read1".to_string(), seq: "ACGT".to_string(), qual: Some("IIII".to_string()), pos: Some(10), ..Default::default() };
+        let copy = rec.clone();
+        assert_eq!(rec, copy);
+        let mut changed = rec.clone();
+        changed.qual = None;
+        assert!(rec != changed);
+        let records: Vec<DnaRecord> = DnaReader::from_path("test/data/fastq.fastq").collect();
+        let again: Vec<DnaRecord> = DnaReader::from_path("test/data/fastq.fastq").collect();
+        assert_eq!(records, again);
+        assert!(format!("{:?}", rec).contains("read1"));
+    }
+
+    #[test]
+    fn test_name_stem() {
+        let mut rec = DnaRecord{ name: "read1/1".to_string(), ..Default::default() };
+        assert!(rec.name_stem() == "read1");
+        rec.name = "read1/2".to_string();
+        assert!(rec.name_stem() == "read1");
+        rec.name = "read1 1:N:0:ACGT".to_string();
+        assert!(rec.name_stem() == "read1");
+        rec.name = "read1/2 2:N:0:ACGT".to_string();
+        assert!(rec.name_stem() == "read1");
+        rec.name = "read1".to_string();
+        assert!(rec.name_stem() == "read1");
+        rec.name = "read1/3".to_string();
+        assert!(rec.name_stem() == "read1/3");
+    }
+
+    #[test]
+    fn test_fasta_windows() {
+        let seq: String = (0..1000).map(|i| ['A', 'C', 'G', 'T'][(i * 7 / 3) % 4]).collect();
+        let mut writer = DnaWriter{ writer: Box::new(FastaWriter::with_line_width("test/data/windows_written.fasta", Uncompressed, Some(60))) };
+        writer.write(&DnaRecord{ name: "chr1".to_string(), seq: seq.clone(), ..Default::default() }).expect("failed to write fasta in test");
+        flush(writer).expect("failed to flush writer in test");
+        let mut reader = FastaReader::new("test/data/windows_written.fasta", Uncompressed).expect("written test data not available");
+        let chunks: Vec<FastaWindow> = reader.windows(300).collect();
+        assert!(chunks.iter().map(|chunk| chunk.seq.len()).collect::<Vec<usize>>() == vec![300, 300, 300, 100]);
+        assert!(chunks.iter().map(|chunk| chunk.start).collect::<Vec<usize>>() == vec![0, 300, 600, 900]);
+        assert!(chunks.iter().all(|rec| rec.name == "chr1"));
+        let joined: String = chunks.iter().map(|rec| rec.seq.as_str()).collect();
+        assert!(joined == seq);
+        // multi record, chunks stop at each record and come back together to the whole sequences
+        let records: Vec<DnaRecord> = DnaReader::from_path("test/data/fasta.fasta").collect();
+        let mut reader = FastaReader::new("test/data/fasta.fasta", Uncompressed).expect("test data not available");
+        let chunks: Vec<FastaWindow> = reader.windows(7).collect();
+        assert!(chunks.iter().all(|chunk| chunk.seq.len() <= 7));
+        for rec in records.iter() {
+            let joined: String = chunks.iter().filter(|chunk| chunk.name == rec.name).map(|chunk| chunk.seq.as_str()).collect();
+            assert!(joined == rec.seq);
+        }
+        let mut reader = FastaReader::new("test/data/fasta.fasta", Uncompressed).expect("test data not available");
+        assert!(reader.windows(0).next().is_none());
+        // blank lines before the first header are skipped the same as for records
+        let mut reader = FastaReader::new("test/data/leading_blank.fasta", Uncompressed).expect("test data not available");
+        let chunks: Vec<FastaWindow> = reader.windows(4).collect();
+        assert!(chunks.iter().map(|chunk| chunk.seq.as_str()).collect::<Vec<&str>>() == vec!["ACGT", "ACGT", "AC"]);
+        assert!(chunks.iter().all(|chunk| chunk.name == "chr1"));
+        let mut reader = FastaReader::new("test/data/blank.fasta", Uncompressed).expect("test data not available");
+        assert!(reader.windows(4).next().is_none());
+    }
+
+    #[test]
+    fn test_fastq_offsets() {
+        let mut contents = String::new();
+        File::open("test/data/fastq.fastq").expect("test data not available").read_to_string(&mut contents).expect("cant read test data");
+        let first_len: usize = contents.lines().take(4).map(|line| line.len() + 1).sum();
+        let mut reader = FastqReader::new("test/data/fastq.fastq", Uncompressed).expect("test data not available");
+        assert!(reader.current_offset() == 0);
+        let (offset, _) = reader.next_with_offset().expect("test data has no records");
+        assert!(offset == 0);
+        let (offset, rec) = reader.next_with_offset().expect("test data has one record");
+        assert!(offset == first_len as u64);
+        assert!(contents[offset as usize..].starts_with(&format!("@{}", rec.name)));
+        // offsets still line up for records guess_encoding already read ahead
+        let mut reader = FastqReader::new("test/data/fastq.fastq", Uncompressed).expect("test data not available");
+        reader.guess_encoding();
+        reader.next();
+        assert!(reader.current_offset() == first_len as u64);
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        let accepted = [("fastq", Fastq), ("fq", Fastq), ("FASTQ", Fastq), ("fasta", Fasta), ("fa", Fasta), ("Fa", Fasta),
+            ("sam", Sam), ("bam", Bam), ("BAM", Bam), ("cram", Cram), ("2bit", TwoBit), ("2Bit", TwoBit), ("raw", Raw), ("txt", Raw), ("tsv", Tsv)];
+        for &(name, ref format) in accepted.iter() {
+            assert!(name.parse::<DnaFormat>().expect("format should parse") == *format);
+        }
+        for format in [Fastq, Fasta, Bam, Sam, Cram, TwoBit, Raw, Tsv].iter() {
+            assert!(format.to_string().parse::<DnaFormat>().expect("display should parse back") == *format);
+        }
+        assert!(Fastq.to_string() == "fastq" && TwoBit.to_string() == "2bit");
+        match "fastz".parse::<DnaFormat>() {
+            Err(DnaError::Parse(msg)) => assert!(msg.contains("fastz")),
+            _ => panic!("fastz is not a format"),
+        }
+    }
+
+    #[test]
+    fn test_reset() {
+        for path in ["test/data/fastq.fastq", "test/data/fasta.fasta", "test/data/fasta.fasta.gz", "test/data/kmers.txt", "test/data/test.2bit"].iter() {
+            let mut reader = DnaReader::from_path(path);
+            let first = reader.next().expect("test data has no records");
+            let rest = reader.by_ref().count();
+            reader.reset().expect("failed to reset in test");
+            let again = reader.next().expect("nothing after reset");
+            assert!(first.name == again.name && first.seq == again.seq);
+            assert!(reader.count() == rest);
+        }
+        let mut reader = DnaReader::from_path("test/data/fasta.fasta");
+        reader.peek();
+        reader.reset().expect("failed to reset in test");
+        assert!(reader.count() == DnaReader::from_path("test/data/fasta.fasta").count());
+        let mut stream = DnaReader::from_read(Box::new(File::open("test/data/fastq.fastq").expect("test data not available")), Fastq, Uncompressed);
+        match stream.reset() {
+            Err(DnaError::Unsupported(_)) => (),
+            _ => panic!("a stream should not be able to reset"),
+        }
+    }
+
+    #[test]
+    fn test_write_counts() {
+        let mut writer = DnaWriter::from_path("test/data/counts_written.fastq.gz");
+        assert!(writer.records_written() == 0 && writer.bytes_written() == 0);
+        writer.write_all(DnaReader::from_path("test/data/fastq.fastq")).expect("failed to write in test");
+        assert!(writer.records_written() == 2 && writer.bytes_written() == 57);
+        writer.write_all(DnaReader::from_path("test/data/paired_R1.fastq")).expect("failed to write in test");
+        // uncompressed, so the two input files added up
+        assert!(writer.records_written() == 5 && writer.bytes_written() == 144);
+        flush(writer).expect("failed to flush writer in test");
+        let mut writer = DnaWriter::from_path("test/data/counts_written.txt");
+        writer.write_all(DnaReader::from_path("test/data/paired_R1.fastq")).expect("failed to write in test");
+        assert!(writer.records_written() == 3 && writer.bytes_written() == 27);
+        let mut chunked = ChunkedFastqWriter::new("test/data/counts_written_chunk", 2);
+        for rec in DnaReader::from_path("test/data/paired_R1.fastq") {
+            chunked.write(&rec).expect("failed to write in test");
+        }
+        assert!(chunked.records_written() == 3 && chunked.bytes_written() == 87);
+    }
+
+    #[test]
+    fn test_chunked_fastq_writer() {
+        let mut writer = ChunkedFastqWriter::new("test/data/chunk_written", 10);
+        for i in 0..25 {
+            let rec = DnaRecord{ name: format!("read{}", i), seq: "ACGT".to_string(), qual: Some("IIII".to_string()), ..Default::default() };
+            writer.write(&rec).expect("failed to write chunk in test");
+        }
+        writer.flush().expect("failed to flush writer in test");
+        assert!(writer.chunks() == 3);
+        let counts: Vec<usize> = (0..3).map(|i| DnaReader::from_path(&format!("test/data/chunk_written_{}.fastq", i)).count()).collect();
+        assert!(counts == vec![10, 10, 5]);
+        let last: Vec<DnaRecord> = DnaReader::from_path("test/data/chunk_written_2.fastq").collect();
+        assert!(last[0].name == "read20" && last[4].name == "read24");
+    }
+
+    #[test]
+    fn test_convert() {
+        let count = convert("test/data/fastq.fastq", "test/data/convert_written.fasta").expect("failed to convert in test");
+        let reader = DnaReader::from_path("test/data/fastq.fastq");
+        let written: Vec<DnaRecord> = DnaReader::from_path("test/data/convert_written.fasta").collect();
+        assert!(count == written.len() as u64);
+        for (rec1, rec2) in reader.zip(written.iter()) {
+            assert!(rec1.name == rec2.name);
+            assert!(rec1.seq == rec2.seq);
+            assert!(rec2.qual.is_none());
+        }
+        let back = convert("test/data/convert_written.fasta", "test/data/convert_written.fastq.gz");
+        match back {
+            Err(DnaError::Unsupported(msg)) => assert!(msg.contains("no quality")),
+            _ => panic!("fasta to fastq should error on the missing quality"),
+        }
+        let count = convert("test/data/fastq.fastq", "test/data/convert_written.fastq.gz").expect("failed to convert in test");
+        let written = DnaReader::from_path("test/data/convert_written.fastq.gz");
+        assert!(written.count() as u64 == count);
+        match convert("test/data/fastq.fastq", "test/data/convert_written.2bit") {
+            Err(DnaError::Unsupported(_)) => (),
+            _ => panic!("2bit output should be unsupported"),
+        }
+        match convert("test/data/fastq.fastq", "test/data/convert_written") {
+            Err(DnaError::UnknownExtension(_)) => (),
+            _ => panic!("no extension should error"),
+        }
+    }
+
+    #[test]
+    fn test_convert_bam_to_fastq() {
+        // unaligned with nothing but names, bases and quals, so nothing is lost
+        assert!(convert("test/data/unmapped.bam", "test/data/unmapped_written.fastq").expect("failed to convert unaligned bam") == 2);
+        let written: Vec<DnaRecord> = DnaReader::from_path("test/data/unmapped_written.fastq").collect();
+        assert!(written[1].name == "unaligned2" && written[1].seq == "GGGTTTAC" && written[1].qual == Some("IIIIHHHH".to_string()));
+        for path in ["test/data/reverse.bam", "test/data/secondary.bam", "test/data/test.bam"].iter() {
+            match convert(path, "test/data/lossy_written.fastq") {
+                Err(DnaError::Unsupported(msg)) => assert!(msg.contains("convert_lossy")),
+                _ => panic!("converting {} to fastq loses alignments, that should be an error", path),
+            }
+        }
+        let all = DnaReader::from_path("test/data/secondary.bam").count() as u64;
+        let primary = convert_lossy("test/data/secondary.bam", "test/data/lossy_written.fastq").expect("failed lossy convert in test");
+        assert!(primary > 0 && primary < all);
+    }
+
+    #[test]
+    fn test_fastq_to_sam() {
+        let reader = DnaReader::from_path("test/data/fastq.fastq");
+        let mut writer = DnaWriter::from_reader_with_header("test/data/fastq_written_header.sam", &reader, minimal_sam_header());
+        writer.write_all(reader).expect("failed to write sam in test");
+        flush(writer).expect("failed to flush writer in test");
+        let mut contents = String::new();
+        File::open("test/data/fastq_written_header.sam").expect("written test data not available").read_to_string(&mut contents).expect("cant read written test data");
+        assert!(contents.starts_with("@HD\tVN:1.6\tSO:unknown"));
+        let original: Vec<DnaRecord> = DnaReader::from_path("test/data/fastq.fastq").collect();
+        let written: Vec<DnaRecord> = DnaReader::from_path("test/data/fastq_written_header.sam").collect();
+        assert!(original.len() == written.len());
+        for (rec1, rec2) in original.iter().zip(written.iter()) {
+            assert!(rec1.name == rec2.name && rec1.seq == rec2.seq && rec1.qual == rec2.qual);
+        }
+    }
+
     #[test]
     fn test_write_fasta() {
         let mut reader = DnaReader::from_path("test/data/fasta.fasta");
@@ -547,7 +5510,7 @@ mod tests {
         for rec in reader {
             writer.write(&rec).expect("failed to write fastq file in test");
         }
-        flush(writer);
+        flush(writer).expect("failed to flush writer in test");
         let reader = DnaReader::from_path("test/data/fasta.fasta");
         let reader2 = DnaReader::from_path("test/data/fasta_written.fasta");
         for (rec1, rec2) in reader.zip(reader2) {
@@ -556,6 +5519,102 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_empty_files() {
+        for path in ["test/data/empty.fasta", "test/data/empty.fastq", "test/data/blank.fasta"].iter() {
+            let mut reader = DnaReader::from_path(path);
+            assert!(reader.next().is_none());
+            assert!(reader.next().is_none());
+        }
+        assert!(DnaReader::from_path("test/data/empty.fasta").count_records() == 0);
+    }
+
+    #[test]
+    fn test_no_final_newline() {
+        let recs: Vec<DnaRecord> = DnaReader::from_path("test/data/no_final_newline.fasta").collect();
+        assert!(recs.len() == 2);
+        assert!(recs[0].seq == "ACGT");
+        assert!(recs[1].seq == "GGCCA");
+        let recs: Vec<DnaRecord> = DnaReader::from_path("test/data/no_final_newline.fastq").collect();
+        assert!(recs.len() == 2);
+        assert!(recs[1].seq == "TTTTTTTT");
+        assert!(recs[1].qual == Some("!!!!!!!!".to_string()));
+    }
+
+    #[test]
+    fn test_crlf() {
+        let recs: Vec<DnaRecord> = DnaReader::from_path("test/data/crlf.fasta").collect();
+        assert!(recs.len() == 2);
+        assert!(recs[0].name == "blah");
+        assert!(recs[0].seq == "ACGTTTTTTTTTTTTTTACGT");
+        assert!(recs[1].name == "nooo" && recs[1].seq == "GGGGGGGGGG");
+        let recs: Vec<DnaRecord> = DnaReader::from_path("test/data/crlf.fastq").collect();
+        assert!(recs.len() == 2);
+        assert!(recs[0].name == "pewpew");
+        assert!(recs[0].seq == "ACTGGTCA");
+        assert!(recs[0].qual == Some("++++++++".to_string()));
+        assert!(recs.iter().all(|rec| !rec.seq.ends_with('\r')));
+    }
+
+    #[test]
+    fn test_fasta_description() {
+        let recs: Vec<DnaRecord> = DnaReader::from_path("test/data/described.fasta").collect();
+        assert!(recs[0].name == "seq1");
+        assert!(recs[0].description == Some("some description here".to_string()));
+        assert!(recs[1].name == "seq2");
+        assert!(recs[1].description.is_none());
+        let rec = DnaReader::from_path("test/data/fasta.fasta").next().expect("no records");
+        assert!(rec.name == "blah");
+        assert!(rec.description.is_none());
+        let mut writer = DnaWriter::from_path("test/data/described_written.fasta");
+        for rec in recs.iter() {
+            writer.write(rec).expect("failed to write described fasta");
+        }
+        flush(writer).expect("failed to flush writer in test");
+        let mut contents = String::new();
+        File::open("test/data/described_written.fasta").expect("written test data not available").read_to_string(&mut contents).expect("cant read written test data");
+        assert!(contents == ">seq1 some description here\nACGT\n>seq2\nGGCC\n");
+    }
+
+    #[test]
+    fn test_fasta_to_fastq_names() {
+        let mut writer = DnaWriter::from_path("test/data/described_written.fastq");
+        for rec in DnaReader::from_path("test/data/described.fasta") {
+            let qual = "I".repeat(rec.len());
+            writer.write(&rec.with_seq_qual(rec.seq.clone(), Some(qual))).expect("failed to write converted fastq");
+        }
+        flush(writer).expect("failed to flush writer in test");
+        let mut contents = String::new();
+        File::open("test/data/described_written.fastq").expect("written test data not available").read_to_string(&mut contents).expect("cant read written test data");
+        let lines: Vec<&str> = contents.lines().collect();
+        assert!(lines[0] == "@seq1 some description here");
+        assert!(lines[4] == "@seq2");
+        let recs: Vec<DnaRecord> = DnaReader::from_path("test/data/described_written.fastq").collect();
+        assert!(recs[1].name == "seq2");
+    }
+
+    #[test]
+    fn test_fasta_line_width() {
+        let rec = DnaRecord{ name: "long".to_string(), seq: "A".repeat(130), qual: None, ..Default::default() };
+        let mut writer = DnaWriter{ writer: Box::new(FastaWriter::with_line_width("test/data/wrapped_written.fasta", Uncompressed, Some(60))) };
+        writer.write(&rec).expect("failed to write wrapped fasta");
+        flush(writer).expect("failed to flush writer in test");
+        let mut contents = String::new();
+        File::open("test/data/wrapped_written.fasta").expect("written test data not available").read_to_string(&mut contents).expect("cant read written test data");
+        let lines: Vec<&str> = contents.lines().collect();
+        assert!(lines.len() == 4);
+        assert!(lines[1].len() == 60 && lines[2].len() == 60 && lines[3].len() == 10);
+        let mut reader = DnaReader::from_path("test/data/wrapped_written.fasta");
+        assert!(reader.next().expect("no wrapped record").seq == rec.seq);
+
+        let mut writer = DnaWriter{ writer: Box::new(FastaWriter::with_line_width("test/data/unwrapped_written.fasta", Uncompressed, None)) };
+        writer.write(&rec).expect("failed to write unwrapped fasta");
+        flush(writer).expect("failed to flush writer in test");
+        let mut contents = String::new();
+        File::open("test/data/unwrapped_written.fasta").expect("written test data not available").read_to_string(&mut contents).expect("cant read written test data");
+        assert!(contents.lines().count() == 2);
+    }
+
     #[test]
     fn test_big_fasta() {
         println!("fork me");